@@ -0,0 +1,17 @@
+#version 140
+
+out vec4 out_color;
+
+uniform sampler2D s_texture;
+
+in vec3 v_color;
+in vec2 v_uv;
+
+// Unlit, so the overlay reads the same at any time of day.
+void main() {
+    vec4 color = texture(s_texture, v_uv);
+    if (color.a < 0.5) {
+        discard;
+    }
+    out_color = color * vec4(v_color, 1.0);
+}
@@ -4,9 +4,40 @@ out vec4 out_color;
 
 uniform sampler2D s_texture;
 
+uniform vec3 ambient;
+uniform vec3 fog_color;
+uniform float fog_density;
+// One point light per column: position and radius, colour and
+// intensity. Unused lights have an intensity of 0.
+uniform mat4 light_pos;
+uniform mat4 light_color;
+// 1 to discard see-through texels, 0 to draw them opaque.
+uniform float alpha_test;
+
 in vec3 v_color;
 in vec2 v_uv;
+in vec3 v_world;
+in float v_distance;
 
 void main() {
-    out_color = texture(s_texture, v_uv);
+    vec4 color = texture(s_texture, v_uv);
+    // Colour index 0 of tiles and sprites is see-through, except on
+    // faces that are drawn opaque.
+    if (color.a < 0.5) {
+        if (alpha_test > 0.5) {
+            discard;
+        }
+        color.a = 1.0;
+    }
+
+    vec3 light = ambient;
+    for (int i = 0; i < 4; i++) {
+        float d = distance(v_world, light_pos[i].xyz);
+        float falloff = max(1.0 - d / max(light_pos[i].w, 0.001), 0.0);
+        light += light_color[i].rgb * light_color[i].a * falloff;
+    }
+
+    vec3 lit = color.rgb * v_color * light;
+    float fog = 1.0 - exp(-fog_density * v_distance);
+    out_color = vec4(mix(lit, fog_color, fog), color.a);
 }
@@ -8,9 +8,14 @@ uniform mat4 projection, view;
 
 out vec3 v_color;
 out vec2 v_uv;
+out vec3 v_world;
+out float v_distance;
 
 void main() {
     v_color = color;
     v_uv = uv;
-    gl_Position = projection * view * vec4(pos, 1.0);
+    v_world = pos;
+    vec4 eye_pos = view * vec4(pos, 1.0);
+    v_distance = length(eye_pos.xyz);
+    gl_Position = projection * eye_pos;
 }
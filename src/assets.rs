@@ -0,0 +1,209 @@
+use std::vec::Vec;
+use std::io::fs;
+use std::io::fs::PathExtensions;
+use piston::image;
+use piston::image::{GenericImage, ImageBuf, Luma};
+
+use json_file;
+use map::Map;
+use style::{Style, CarInfo, ObjectInfo, TILE_SIZE};
+
+/// Resolves game files from a base game directory and an ordered list
+/// of mod directories.
+///
+/// A mod replaces a whole file by shipping one with the same name.
+/// Maps can also be replaced by `<name>.json` saved with
+/// `Map::save_json_file`. Styles can be patched entry by entry with a
+/// `<name>.patch.json` `StylePatch`. Patches of every mod are applied
+/// in order, so later mods win.
+#[deriving(Clone)]
+pub struct Assets {
+    pub base: Path,
+    pub mods: Vec<Path>
+}
+
+/// Changes to individual entries of a style.
+#[deriving(Decodable)]
+pub struct StylePatch {
+    pub car_info: Option<Vec<CarInfoPatch>>,
+    pub object_info: Option<Vec<ObjectInfoPatch>>,
+    pub tiles: Option<Vec<TilePatch>>
+}
+
+/// Replaces or appends car `index`.
+#[deriving(Decodable)]
+pub struct CarInfoPatch {
+    pub index: uint,
+    pub car: CarInfo
+}
+
+/// Replaces or appends object `index`.
+#[deriving(Decodable)]
+pub struct ObjectInfoPatch {
+    pub index: uint,
+    pub object: ObjectInfo
+}
+
+/// Replaces the pixels of tile `index` with a 64x64 greyscale PNG of
+/// colour indices, relative to the mod directory.
+#[deriving(Decodable)]
+pub struct TilePatch {
+    pub index: uint,
+    pub image: String
+}
+
+impl Assets {
+    pub fn new(base: Path) -> Assets {
+        Assets {
+            base: base,
+            mods: Vec::new()
+        }
+    }
+
+    /// Adds a mod directory on top of the previously added ones.
+    pub fn add_mod(&mut self, dir: Path) {
+        self.mods.push(dir);
+    }
+
+    /// Adds every directory in `dir` as a mod, in name order.
+    pub fn add_mods_in(&mut self, dir: &Path) {
+        let mut dirs = match fs::readdir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return
+        };
+        dirs.sort();
+        for d in dirs.into_iter() {
+            if d.is_dir() {
+                println!("Using mod {}", d.display());
+                self.add_mod(d);
+            }
+        }
+    }
+
+    /// Returns the path of `name` in the last mod that has it, or in
+    /// the base directory.
+    pub fn resolve(&self, name: &str) -> Path {
+        match self.find_in_mods(name) {
+            Some(path) => path,
+            None => self.base.join(name)
+        }
+    }
+
+    /// Returns every path `name` could be resolved to, whether it
+    /// exists or not: the base directory first, then each mod.
+    pub fn candidates(&self, name: &str) -> Vec<Path> {
+        let mut paths = vec!(self.base.join(name));
+        for dir in self.mods.iter() {
+            paths.push(dir.join(name));
+        }
+        paths
+    }
+
+    /// Returns `true` if `name` exists in a mod or the base directory.
+    pub fn exists(&self, name: &str) -> bool {
+        self.resolve(name).is_file()
+    }
+
+    /// Loads map `name` from the last mod that has it or a JSON
+    /// replacement of it, or from the base directory. Within one mod
+    /// the JSON replacement wins.
+    pub fn load_map(&self, name: &str) -> Result<Map, String> {
+        let json_name = format!("{}.json", name);
+        for dir in self.mods.iter().rev() {
+            let json = dir.join(json_name.as_slice());
+            if json.is_file() {
+                return Map::from_json_file(try!(path_str(&json)));
+            }
+            let path = dir.join(name);
+            if path.is_file() {
+                return load_binary_map(&path);
+            }
+        }
+        load_binary_map(&self.base.join(name))
+    }
+
+    /// Loads style `name` and applies the patches of every mod.
+    pub fn load_style(&self, name: &str) -> Result<Style, String> {
+        let path = self.resolve(name);
+        let mut style = match Style::from_file(try!(path_str(&path))) {
+            Ok(style) => style,
+            Err(why) => return Err(format!("{}", why))
+        };
+
+        let patch_name = format!("{}.patch.json", name);
+        for dir in self.mods.iter() {
+            let path = dir.join(patch_name.as_slice());
+            if path.is_file() {
+                println!("Applying style patch {}", path.display());
+                try!(apply_patch(&mut style, dir, &path));
+            }
+        }
+        Ok(style)
+    }
+
+    fn find_in_mods(&self, name: &str) -> Option<Path> {
+        self.mods.iter().rev()
+            .map(|dir| dir.join(name))
+            .find(|path| path.is_file())
+    }
+}
+
+/// Returns `path` as a string, for the loaders taking file names.
+pub fn path_str(path: &Path) -> Result<&str, String> {
+    match path.as_str() {
+        Some(s) => Ok(s),
+        None => Err(format!("Path {} is not valid UTF-8", path.display()))
+    }
+}
+
+fn load_binary_map(path: &Path) -> Result<Map, String> {
+    Map::from_file(try!(path_str(path))).map_err(|why| why.desc.to_string())
+}
+
+fn apply_patch(style: &mut Style, dir: &Path, path: &Path) -> Result<(), String> {
+    let patch: StylePatch = try!(json_file::load(path));
+
+    for p in patch.car_info.unwrap_or(Vec::new()).into_iter() {
+        try!(p.car.validate().map_err(|why| format!("Car {}: {}", p.index, why)));
+        try!(patch_entry(&mut style.car_info, p.index, p.car));
+    }
+    for p in patch.object_info.unwrap_or(Vec::new()).into_iter() {
+        try!(p.object.validate().map_err(|why| format!("Object {}: {}", p.index, why)));
+        try!(patch_entry(&mut style.object_info, p.index, p.object));
+    }
+    for p in patch.tiles.unwrap_or(Vec::new()).into_iter() {
+        let img = try!(load_luma8(&dir.join(p.image.as_slice())));
+        try!(style.set_tile(p.index, &img));
+    }
+    Ok(())
+}
+
+/// Replaces entry `index` of `entries`, or appends it if `index` is
+/// one past the end.
+fn patch_entry<T>(entries: &mut Vec<T>, index: uint, entry: T) -> Result<(), String> {
+    if index < entries.len() {
+        *entries.get_mut(index) = entry;
+    } else if index == entries.len() {
+        entries.push(entry);
+    } else {
+        return Err(format!("Patch index {} out of range, style has {}",
+                           index, entries.len()));
+    }
+    Ok(())
+}
+
+fn load_luma8(path: &Path) -> Result<ImageBuf<Luma<u8>>, String> {
+    match image::open(path) {
+        Ok(image::ImageLuma8(img)) => {
+            let (w, h) = img.dimensions();
+            if w as uint != TILE_SIZE || h as uint != TILE_SIZE {
+                return Err(format!("Tile '{}' is {}x{}, expected {}x{}",
+                                   path.display(), w, h, TILE_SIZE, TILE_SIZE));
+            }
+            Ok(img)
+        },
+        Ok(img) => Err(format!("Unsupported color type {} in '{}'",
+                               img.color(), path.display())),
+        Err(e) => Err(format!("Could not load '{}': {}", path.display(), e))
+    }
+}
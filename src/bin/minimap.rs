@@ -0,0 +1,53 @@
+//! Writes a top-down overview of a map to a PNG.
+//!
+//! Usage: `minimap <map> <output> [style]`
+//!
+//! Without a style blocks are coloured by type, one pixel per block.
+//! With a style every block is drawn with its lid tile at 8 pixels.
+
+extern crate gta;
+
+use std::os;
+
+use gta::map::Map;
+use gta::style::Style;
+use gta::minimap;
+use gta::minimap::MinimapOptions;
+use gta::renderer::capture::save_png;
+
+fn main() {
+    let args = os::args();
+    if args.len() != 3 && args.len() != 4 {
+        println!("Usage: {} <map> <output> [style]", args[0]);
+        os::set_exit_status(1);
+        return;
+    }
+
+    let map = match Map::from_file(args[1].as_slice()) {
+        Err(why) => panic!("Could not load map: {}", why.desc),
+        Ok(map) => map
+    };
+    let style = if args.len() == 4 {
+        match Style::from_file(args[3].as_slice()) {
+            Err(why) => panic!("Could not load style: {}", why),
+            Ok(style) => Some(style)
+        }
+    } else {
+        None
+    };
+
+    let mut opts = MinimapOptions::default();
+    if style.is_some() {
+        opts.scale = 8;
+        opts.fill = minimap::LidTile;
+    }
+
+    let img = minimap::render(&map, style.as_ref(), &opts);
+    match save_png(img, &Path::new(args[2].as_slice())) {
+        Err(why) => {
+            println!("Could not save minimap: {}", why);
+            os::set_exit_status(1);
+        },
+        Ok(()) => {}
+    }
+}
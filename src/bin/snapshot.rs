@@ -0,0 +1,61 @@
+//! Renders a map snapshot to a PNG without a GPU.
+//!
+//! Usage: `snapshot <map> <texture> <output> [width height]`
+
+extern crate cam;
+extern crate gta;
+
+use std::os;
+
+use gta::map::Map;
+use gta::chunk::Chunk;
+use gta::renderer::load_rgba8;
+use gta::renderer::software::SoftwareRenderer;
+
+fn main() {
+    let args = os::args();
+    if args.len() != 4 && args.len() != 6 {
+        println!("Usage: {} <map> <texture> <output> [width height]", args[0]);
+        os::set_exit_status(1);
+        return;
+    }
+
+    let (width, height) = if args.len() == 6 {
+        (from_str::<uint>(args[4].as_slice()).expect("Invalid width"),
+         from_str::<uint>(args[5].as_slice()).expect("Invalid height"))
+    } else {
+        (640, 480)
+    };
+
+    let map = match Map::from_file(args[1].as_slice()) {
+        Err(why) => panic!("Could not load map: {}", why.desc),
+        Ok(map) => map
+    };
+    let texture = match load_rgba8(&Path::new(args[2].as_slice())) {
+        Err(why) => panic!("{}", why),
+        Ok(texture) => texture
+    };
+    let chunks = Chunk::all_from_map(&map, None);
+
+    let projection = cam::CameraPerspective {
+        fov: 90.0f32,
+        near_clip: 0.1,
+        far_clip: 1000.0,
+        aspect_ratio: (width as f32) / (height as f32)
+    }.projection();
+
+    // Look down on the centre of the map from the south.
+    let mut camera = cam::Camera::new([128.0f32, 24.0, 152.0]);
+    camera.look_at([128.0, 0.0, 128.0]);
+
+    let mut r = SoftwareRenderer::new(width, height);
+    for chunk in chunks.iter() {
+        r.draw(chunk.verts.as_slice(), chunk.indices.as_slice(),
+               projection, camera.orthogonal(), &texture);
+    }
+
+    match r.save(&Path::new(args[3].as_slice())) {
+        Err(why) => panic!("Could not save snapshot: {}", why),
+        Ok(()) => {}
+    }
+}
@@ -0,0 +1,110 @@
+//! Dumps the contents of a style to PNG files and a JSON manifest.
+//!
+//! Usage: `style_dump <style> <output directory> [font files...]`
+
+extern crate piston;
+extern crate gta;
+
+use std::os;
+use std::io;
+use std::io::{File, IoResult};
+use std::io::fs;
+use piston::image;
+use piston::image::{ImageBuf, Rgba};
+
+use gta::assets;
+use gta::style::Style;
+use gta::font::Font;
+
+fn main() {
+    let args = os::args();
+    if args.len() < 3 {
+        println!("Usage: {} <style> <output directory> [font files...]", args[0]);
+        os::set_exit_status(1);
+        return;
+    }
+
+    let style = match Style::from_file(args[1].as_slice()) {
+        Err(why) => {
+            println!("Could not load style: {}", why);
+            os::set_exit_status(1);
+            return;
+        },
+        Ok(style) => style
+    };
+    let out = Path::new(args[2].as_slice());
+
+    match dump(&style, &out, args.slice_from(3)) {
+        Err(why) => {
+            println!("Could not dump style: {}", why);
+            os::set_exit_status(1);
+        },
+        Ok(()) => {}
+    }
+}
+
+fn dump(style: &Style, out: &Path, fonts: &[String]) -> IoResult<()> {
+    let tiles = out.join("tiles");
+    try!(fs::mkdir_recursive(&tiles, io::USER_RWX));
+    for tile in range(0, style.num_tiles()) {
+        let img = try!(style.tile_image(tile, 0).map_err(|why| {
+            invalid_input("Could not decode style", why)
+        }));
+        try!(save(img, &tiles.join(format!("{:03}.png", tile))));
+    }
+
+    let sprites = out.join("sprites");
+    try!(fs::mkdir_recursive(&sprites, io::USER_RWX));
+    for sprite in range(0, style.sprite_info.len()) {
+        let img = try!(style.sprite_image(sprite).map_err(|why| {
+            invalid_input("Could not decode style", why)
+        }));
+        try!(save(img, &sprites.join(format!("{:04}.png", sprite))));
+    }
+
+    for (n, filename) in fonts.iter().enumerate() {
+        let font = try!(Font::from_file(filename.as_slice()));
+        let palette = match style.font_palette(n) {
+            Ok(palette) => palette,
+            Err(_) => font.palette.clone()
+        };
+
+        let dir = match Path::new(filename.as_slice()).filestem() {
+            Some(stem) => out.join("fonts").join(stem),
+            None => return Err(invalid_input("Invalid font path", filename.clone()))
+        };
+        try!(fs::mkdir_recursive(&dir, io::USER_RWX));
+        for (i, glyph) in font.glyphs.iter().enumerate() {
+            let img = glyph.to_image(palette.as_slice());
+            try!(save(img, &dir.join(format!("{:03}.png", i))));
+        }
+    }
+
+    // The manifest can be loaded back with `StyleMetadata::from_json_file`.
+    let manifest = out.join("manifest.json");
+    let manifest = try!(assets::path_str(&manifest).map_err(|why| {
+        invalid_input("Invalid output path", why)
+    }));
+    style.metadata().save_json_file(manifest)
+}
+
+fn save(img: ImageBuf<Rgba<u8>>, path: &Path) -> IoResult<()> {
+    let fout = try!(File::create(path));
+    match image::ImageRgba8(img).save(fout, image::PNG) {
+        Ok(()) => Ok(()),
+        Err(why) => Err(io::IoError {
+            kind: io::OtherIoError,
+            desc: "Could not encode PNG",
+            detail: Some(format!("{}: {}", path.display(), why))
+        })
+    }
+}
+
+/// Wraps an error decoding the style or naming files as an I/O error.
+fn invalid_input(desc: &'static str, why: String) -> io::IoError {
+    io::IoError {
+        kind: io::InvalidInput,
+        desc: desc,
+        detail: Some(why)
+    }
+}
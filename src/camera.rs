@@ -0,0 +1,70 @@
+use std::num::Float;
+use cam::Camera;
+
+/// Settings for the `TopDown` camera. Heights are in blocks above
+/// the target.
+pub struct TopDownSettings {
+    /// The height when the target stands still.
+    pub min_height: f32,
+    /// The height at full speed.
+    pub max_height: f32,
+    /// How much higher the camera goes per block per second of target
+    /// speed.
+    pub height_per_speed: f32,
+    /// How quickly the camera reaches its wanted height, per second.
+    pub smoothing: f32
+}
+
+impl TopDownSettings {
+    pub fn default() -> TopDownSettings {
+        TopDownSettings {
+            min_height: 12.0,
+            max_height: 30.0,
+            height_per_speed: 0.8,
+            smoothing: 3.0
+        }
+    }
+}
+
+/// The camera of the original game. It looks straight down at its
+/// target with north up and rises as the target speeds up.
+pub struct TopDown {
+    pub target: [f32, ..3],
+    pub height: f32,
+    pub settings: TopDownSettings
+}
+
+impl TopDown {
+    pub fn new(target: [f32, ..3], settings: TopDownSettings) -> TopDown {
+        TopDown {
+            target: target,
+            height: settings.min_height,
+            settings: settings
+        }
+    }
+
+    /// Moves to `target`, which has moved there over `dt` seconds.
+    pub fn follow(&mut self, target: [f32, ..3], dt: f32) {
+        if dt > 0.0 {
+            let (dx, dz) = (target[0] - self.target[0], target[2] - self.target[2]);
+            let speed = (dx * dx + dz * dz).sqrt() / dt;
+
+            let s = &self.settings;
+            let wanted = (s.min_height + speed * s.height_per_speed).min(s.max_height);
+            let t = (s.smoothing * dt).min(1.0);
+            self.height += (wanted - self.height) * t;
+        }
+        self.target = target;
+    }
+
+    pub fn camera(&self) -> Camera<f32> {
+        let mut camera = Camera::new([self.target[0],
+                                      self.target[1] + self.height,
+                                      self.target[2]]);
+        // The map is mirrored along x, so east is -x and north is -z.
+        camera.forward = [0.0, -1.0, 0.0];
+        camera.up = [0.0, 0.0, -1.0];
+        camera.right = [-1.0, 0.0, 0.0];
+        camera
+    }
+}
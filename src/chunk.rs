@@ -1,6 +1,10 @@
 use std::vec::Vec;
 
+use std::iter::range_step;
+
 use renderer::Vertex;
+use renderer::frustum::Aabb;
+use renderer::tile_atlas::TileAtlas;
 use map;
 use map::Map;
 use map::block;
@@ -9,16 +13,23 @@ static X: uint = 0;
 static Y: uint = 1;
 static MAX_WIDTH: uint = 256;
 static MAX_HEIGHT: uint = 256;
-static CHUNK_SIZE: uint = 255;
+pub static CHUNK_SIZE: uint = 32;
 
 pub struct Chunk {
     pub pos: [uint, ..2],
     pub verts: Vec<Vertex>,
-    pub indices: Vec<u32>
+    pub indices: Vec<u32>,
+    /// The indices of the see-through faces of flat blocks, to be
+    /// drawn alpha tested after `indices`.
+    pub transparent_indices: Vec<u32>,
+    /// The bounds of `verts`, `None` if the chunk is all air.
+    pub bounds: Option<Aabb>
 }
 
 impl Chunk {
-    pub fn from_map(map: &Map, offset: [uint, ..2]) -> Option<Chunk> {
+    /// Builds the blocks of `map` starting at `offset`, textured with
+    /// `tiles` if given, see `block_data::from_block`.
+    pub fn from_map(map: &Map, offset: [uint, ..2], tiles: Option<&TileAtlas>) -> Option<Chunk> {
         assert!(offset[X] % CHUNK_SIZE == 0);
         assert!(offset[Y] % CHUNK_SIZE == 0);
 
@@ -29,6 +40,7 @@ impl Chunk {
         let mut index_offset = 0;
         let mut v = Vec::with_capacity(CHUNK_SIZE * CHUNK_SIZE * 36);
         let mut i = Vec::with_capacity(CHUNK_SIZE * CHUNK_SIZE * 20);
+        let mut t = Vec::new();
         for x in range(offset[X], offset[X] + CHUNK_SIZE) {
             for y in range(offset[Y], offset[Y] + CHUNK_SIZE) {
                 let h = map.blocks[x][y].len();
@@ -40,29 +52,47 @@ impl Chunk {
                         continue;
                     }
 
-                    let (verts, indices) = map::block_data::from_block(
+                    let (verts, indices, transparent) = map::block_data::from_block(
                         block,
                         [256.0 - x as f32, z as f32, y as f32],
-                        index_offset
+                        index_offset,
+                        tiles
                     );
 
                     index_offset += verts.len() as u32;
                     
                     v.push_all(verts.as_slice());
                     i.push_all(indices.as_slice());
+                    t.push_all(transparent.as_slice());
                 }
             }
         }
 
         Some(Chunk {
             pos: offset,
+            bounds: Aabb::from_points(v.iter().map(|v| &v.pos)),
             verts: v,
-            indices: i
+            indices: i,
+            transparent_indices: t
         })
     }
+
+    /// Builds every chunk of `map` that has something to draw.
+    pub fn all_from_map(map: &Map, tiles: Option<&TileAtlas>) -> Vec<Chunk> {
+        let mut chunks = Vec::new();
+        for x in range_step(0, MAX_WIDTH, CHUNK_SIZE) {
+            for y in range_step(0, MAX_HEIGHT, CHUNK_SIZE) {
+                match Chunk::from_map(map, [x, y], tiles) {
+                    Some(ref chunk) if chunk.verts.is_empty() => {},
+                    Some(chunk) => chunks.push(chunk),
+                    None => {}
+                }
+            }
+        }
+        chunks
+    }
 }
 
 fn offset_in_range(offset: [uint, ..2]) -> bool {
-    offset[X] + CHUNK_SIZE < MAX_WIDTH && offset[X] >= 0 &&
-        offset[Y] + CHUNK_SIZE < MAX_HEIGHT && offset[Y] >= 0
+    offset[X] + CHUNK_SIZE <= MAX_WIDTH && offset[Y] + CHUNK_SIZE <= MAX_HEIGHT
 }
@@ -0,0 +1,304 @@
+use std::vec::Vec;
+use std::from_str::FromStr;
+use std::io::File;
+use std::io::fs::PathExtensions;
+use serialize::json;
+use getopts::{optopt, optmulti, optflag, getopts, usage, OptGroup};
+
+use level;
+use level::Level;
+use lighting;
+use lighting::LightingPreset;
+
+/// The config file read when `--config` is not given.
+static DEFAULT_CONFIG: &'static str = "gta.json";
+
+/// The multisample counts a window can be created with.
+static SAMPLE_COUNTS: [u8, ..5] = [0, 2, 4, 8, 16];
+
+/// Game options, read from a JSON config file and overridden by
+/// command-line flags.
+pub struct Config {
+    /// The directory holding the original game files.
+    pub data_dir: Path,
+    pub level: Level,
+    /// Mod directories, applied in order on top of those found in
+    /// `<data_dir>/mods`.
+    pub mods: Vec<Path>,
+    pub settings: Settings,
+    /// The map object types that are street lamps, lit at night, or
+    /// `None` for those shaped like lamps, see `Style::lamp_objects`.
+    pub lamp_objects: Option<Vec<u8>>,
+    /// Render this many frames offscreen with a fixed time step,
+    /// save them to `capture_dir` and exit.
+    pub capture: Option<uint>,
+    pub capture_dir: Path
+}
+
+/// Window and display options.
+#[deriving(Clone, Show)]
+pub struct Settings {
+    /// The window size, or the screen resolution in fullscreen.
+    pub width: u32,
+    pub height: u32,
+    pub fullscreen: bool,
+    /// Whether to wait for the display's refresh between frames.
+    pub vsync: bool,
+    /// The number of samples for multisample anti-aliasing, 0 for none.
+    pub samples: u8,
+    /// The vertical field of view in degrees.
+    pub fov: f32,
+    pub lighting: LightingPreset
+}
+
+impl Settings {
+    pub fn default() -> Settings {
+        Settings {
+            width: 1920,
+            height: 1080,
+            fullscreen: true,
+            vsync: true,
+            samples: 4,
+            fov: 90.0,
+            lighting: lighting::DayNight
+        }
+    }
+
+    /// Checks the options the window and projection can't work with,
+    /// wherever they were set.
+    fn validate(&self) -> Result<(), String> {
+        if self.width == 0 || self.height == 0 {
+            return Err(format!("Invalid resolution {}x{}", self.width, self.height));
+        }
+        if !SAMPLE_COUNTS.contains(&self.samples) {
+            return Err(format!("Invalid sample count {}, expected one of {}",
+                               self.samples, SAMPLE_COUNTS.as_slice()));
+        }
+        if !(self.fov > 0.0 && self.fov < 180.0) {
+            return Err(format!("Invalid field of view {}, expected 0 to 180 degrees",
+                               self.fov));
+        }
+        Ok(())
+    }
+}
+
+/// The config file. Every field is optional.
+#[deriving(Decodable)]
+struct ConfigFile {
+    data_dir: Option<String>,
+    level: Option<String>,
+    mods: Option<Vec<String>>,
+    video: Option<VideoFile>,
+    lamp_objects: Option<Vec<u8>>
+}
+
+/// The `video` section of the config file.
+#[deriving(Decodable)]
+struct VideoFile {
+    width: Option<u32>,
+    height: Option<u32>,
+    fullscreen: Option<bool>,
+    vsync: Option<bool>,
+    samples: Option<u8>,
+    fov: Option<f32>,
+    lighting: Option<String>
+}
+
+impl Config {
+    pub fn default() -> Config {
+        Config {
+            data_dir: Path::new("data"),
+            level: level::NewYork,
+            mods: Vec::new(),
+            settings: Settings::default(),
+            lamp_objects: None,
+            capture: None,
+            capture_dir: Path::new("capture")
+        }
+    }
+
+    /// Builds the config from the program arguments. Returns `Ok(None)`
+    /// if only the usage was asked for.
+    pub fn from_args(args: &[String]) -> Result<Option<Config>, String> {
+        let opts = options();
+        let matches = match getopts(args.tail(), opts.as_slice()) {
+            Ok(m) => m,
+            Err(why) => return Err(format!("{}", why))
+        };
+
+        if matches.opt_present("help") {
+            println!("{}", usage(format!("Usage: {} [options]", args[0]).as_slice(),
+                                 opts.as_slice()));
+            return Ok(None);
+        }
+
+        let mut config = Config::default();
+        match matches.opt_str("config") {
+            Some(path) => try!(config.merge_file(&Path::new(path))),
+            None => {
+                let path = Path::new(DEFAULT_CONFIG);
+                if path.is_file() {
+                    try!(config.merge_file(&path));
+                }
+            }
+        }
+
+        match matches.opt_str("data") {
+            Some(dir) => config.data_dir = Path::new(dir),
+            None => {}
+        }
+        match matches.opt_str("level") {
+            Some(name) => config.level = try!(parse_level(name.as_slice())),
+            None => {}
+        }
+        for dir in matches.opt_strs("mod").into_iter() {
+            config.mods.push(Path::new(dir));
+        }
+        match matches.opt_str("capture") {
+            Some(n) => config.capture = Some(try!(parse_number(n.as_slice(), "frame count"))),
+            None => {}
+        }
+        match matches.opt_str("capture-dir") {
+            Some(dir) => config.capture_dir = Path::new(dir),
+            None => {}
+        }
+
+        {
+            let settings = &mut config.settings;
+            match matches.opt_str("resolution") {
+                Some(res) => {
+                    let (w, h) = try!(parse_resolution(res.as_slice()));
+                    settings.width = w;
+                    settings.height = h;
+                },
+                None => {}
+            }
+            if matches.opt_present("fullscreen") {
+                settings.fullscreen = true;
+            }
+            if matches.opt_present("windowed") {
+                settings.fullscreen = false;
+            }
+            if matches.opt_present("vsync") {
+                settings.vsync = true;
+            }
+            if matches.opt_present("no-vsync") {
+                settings.vsync = false;
+            }
+            match matches.opt_str("samples") {
+                Some(n) => settings.samples = try!(parse_number(n.as_slice(), "sample count")),
+                None => {}
+            }
+            match matches.opt_str("fov") {
+                Some(fov) => settings.fov = try!(parse_number(fov.as_slice(), "field of view")),
+                None => {}
+            }
+            match matches.opt_str("lighting") {
+                Some(name) => settings.lighting = try!(parse_lighting(name.as_slice())),
+                None => {}
+            }
+        }
+
+        try!(config.settings.validate());
+        Ok(Some(config))
+    }
+
+    /// Overrides the options set in config file `path`.
+    fn merge_file(&mut self, path: &Path) -> Result<(), String> {
+        let s = match File::open(path).read_to_string() {
+            Ok(s) => s,
+            Err(why) => return Err(format!("Could not read {}: {}", path.display(), why))
+        };
+        let file: ConfigFile = match json::decode(s.as_slice()) {
+            Ok(file) => file,
+            Err(why) => return Err(format!("Could not parse {}: {}", path.display(), why))
+        };
+
+        match file.data_dir {
+            Some(dir) => self.data_dir = Path::new(dir),
+            None => {}
+        }
+        match file.level {
+            Some(name) => self.level = try!(parse_level(name.as_slice())),
+            None => {}
+        }
+        for dir in file.mods.unwrap_or(Vec::new()).into_iter() {
+            self.mods.push(Path::new(dir));
+        }
+
+        match file.video {
+            Some(video) => {
+                let s = &mut self.settings;
+                s.width = video.width.unwrap_or(s.width);
+                s.height = video.height.unwrap_or(s.height);
+                s.fullscreen = video.fullscreen.unwrap_or(s.fullscreen);
+                s.vsync = video.vsync.unwrap_or(s.vsync);
+                s.samples = video.samples.unwrap_or(s.samples);
+                s.fov = video.fov.unwrap_or(s.fov);
+                match video.lighting {
+                    Some(name) => s.lighting = try!(parse_lighting(name.as_slice())),
+                    None => {}
+                }
+            },
+            None => {}
+        }
+        match file.lamp_objects {
+            Some(types) => self.lamp_objects = Some(types),
+            None => {}
+        }
+        Ok(())
+    }
+}
+
+fn options() -> Vec<OptGroup> {
+    vec!(
+        optopt("c", "config", "read options from FILE", "FILE"),
+        optopt("d", "data", "game data directory", "DIR"),
+        optopt("l", "level", "nyc, san-andreas or vice-city", "LEVEL"),
+        optmulti("m", "mod", "apply the mod in DIR, may be repeated", "DIR"),
+        optopt("r", "resolution", "window size, e.g. 1280x720", "WxH"),
+        optflag("", "fullscreen", "run fullscreen"),
+        optflag("", "windowed", "run in a window"),
+        optflag("", "vsync", "wait for vertical sync"),
+        optflag("", "no-vsync", "don't wait for vertical sync"),
+        optopt("", "samples", "multisample anti-aliasing samples, 0 for none", "N"),
+        optopt("", "fov", "vertical field of view in degrees", "DEG"),
+        optopt("", "lighting", "classic or day-night", "PRESET"),
+        optopt("", "capture", "save N frames rendered offscreen and exit", "N"),
+        optopt("", "capture-dir", "directory for captured frames", "DIR"),
+        optflag("h", "help", "print this help")
+    )
+}
+
+fn parse_level(name: &str) -> Result<Level, String> {
+    match Level::from_name(name) {
+        Some(level) => Ok(level),
+        None => Err(format!("Unknown level '{}'", name))
+    }
+}
+
+fn parse_lighting(name: &str) -> Result<LightingPreset, String> {
+    match LightingPreset::from_name(name) {
+        Some(preset) => Ok(preset),
+        None => Err(format!("Unknown lighting preset '{}'", name))
+    }
+}
+
+/// Parses a resolution such as `1280x720`.
+fn parse_resolution(s: &str) -> Result<(u32, u32), String> {
+    let parts: Vec<&str> = s.split('x').collect();
+    if parts.len() != 2 {
+        return Err(format!("Invalid resolution '{}', expected WIDTHxHEIGHT", s));
+    }
+    match (from_str(parts[0]), from_str(parts[1])) {
+        (Some(w), Some(h)) if w > 0 && h > 0 => Ok((w, h)),
+        _ => Err(format!("Invalid resolution '{}'", s))
+    }
+}
+
+fn parse_number<T: FromStr>(s: &str, what: &str) -> Result<T, String> {
+    match from_str(s) {
+        Some(n) => Ok(n),
+        None => Err(format!("Invalid {} '{}'", what, s))
+    }
+}
@@ -0,0 +1,284 @@
+use std::f32::consts::PI;
+use std::num::Float;
+
+use piston::image::{ImageBuf, Rgba};
+
+use font::Font;
+use map::{Map, block};
+use map::block::Block;
+use map::location;
+use map::object::UNITS_PER_BLOCK;
+use map::position::Position;
+use renderer::Vertex;
+use renderer::sprite_batch::{Region, SpriteAtlas};
+use style::Style;
+use style::vehicle_spec::ANGLE_UNITS_PER_TURN;
+
+/// The number of blocks along each side of a map.
+static MAP_SIZE: uint = 256;
+/// The height zones and their names are drawn at, above the tallest
+/// buildings.
+static ZONE_HEIGHT: f32 = 6.0;
+/// The width of lines in blocks.
+static LINE_WIDTH: f32 = 0.05;
+/// How far above surfaces lines are drawn.
+static LIFT: f32 = 0.02;
+/// The size of text: font pixels per block.
+static TEXT_PIXELS_PER_BLOCK: f32 = 16.0;
+/// The size of an object without style info, in blocks.
+static DEFAULT_OBJECT_SIZE: f32 = 0.25;
+
+/// Which map data the overlay shows.
+pub struct OverlayOptions {
+    /// Allowed traffic directions of each block.
+    pub arrows: bool,
+    /// An outline on sloped lids, coloured by slope length.
+    pub slopes: bool,
+    pub zones: bool,
+    pub routes: bool,
+    pub locations: bool,
+    /// Object bounding boxes.
+    pub objects: bool
+}
+
+impl OverlayOptions {
+    pub fn default() -> OverlayOptions {
+        OverlayOptions {
+            arrows: true,
+            slopes: true,
+            zones: true,
+            routes: true,
+            locations: true,
+            objects: true
+        }
+    }
+}
+
+/// Lines and labels showing the data a map carries, meant to be drawn
+/// on top of the world without depth testing.
+///
+/// The geometry is textured with `atlas`: region 0 is solid white for
+/// lines, the rest are zone names.
+pub struct DebugOverlay {
+    pub atlas: SpriteAtlas,
+    pub verts: Vec<Vertex>,
+    pub indices: Vec<u32>
+}
+
+impl DebugOverlay {
+    /// Builds the overlay of `map`. `style` gives object sizes and
+    /// `font` with its palette draws zone names; without them objects
+    /// get a default size and zones are left unnamed. The atlas of
+    /// names may be at most `max_texture_size` pixels wide or tall.
+    pub fn build(map: &Map, style: Option<&Style>, font: Option<(&Font, &[Rgba<u8>])>,
+                 opts: &OverlayOptions, max_texture_size: u32) -> Result<DebugOverlay, String> {
+        let mut images = vec!(ImageBuf::from_pixel(4, 4, Rgba(255, 255, 255, 255)));
+        // The atlas region of each zone's name.
+        let mut names = Vec::with_capacity(map.zones.len());
+        for zone in map.zones.iter() {
+            match font {
+                Some((font, palette)) if opts.zones && !zone.name.is_empty() => {
+                    images.push(font.render_text(zone.name.as_slice(), palette));
+                    names.push(Some(images.len() - 1));
+                },
+                _ => names.push(None)
+            }
+        }
+
+        let mut o = DebugOverlay {
+            atlas: try!(SpriteAtlas::from_images(images.as_slice(), max_texture_size)),
+            verts: Vec::new(),
+            indices: Vec::new()
+        };
+
+        if opts.arrows || opts.slopes {
+            for x in range(0, MAP_SIZE) {
+                for y in range(0, MAP_SIZE) {
+                    let (z, top) = match top_block(map, x, y) {
+                        Some(b) => b,
+                        None => continue
+                    };
+                    let h = z as f32 + 1.0 + LIFT;
+                    if opts.arrows {
+                        o.arrows(x, y, h, &top);
+                    }
+                    if opts.slopes && top.get_slope_type() != 0 {
+                        let color = slope_color(top.get_slope_type());
+                        o.rect([x as f32 + 0.1, y as f32 + 0.1], [0.8, 0.8], h, color);
+                    }
+                }
+            }
+        }
+
+        if opts.zones {
+            for (zone, name) in map.zones.iter().zip(names.iter()) {
+                let a = &zone.area;
+                o.rect([a.x as f32, a.y as f32], [a.width as f32, a.height as f32],
+                       ZONE_HEIGHT, [1.0, 1.0, 0.0]);
+                match *name {
+                    Some(region) => {
+                        let region = o.atlas.regions[region].clone();
+                        o.label([a.x as f32 + 0.25, a.y as f32 + 0.25], ZONE_HEIGHT, &region);
+                    },
+                    None => {}
+                }
+            }
+        }
+
+        if opts.routes {
+            for route in map.routes.iter() {
+                for pair in route.points.as_slice().windows(2) {
+                    let point = |p: &Position| {
+                        world([p.x as f32 + 0.5, p.y as f32 + 0.5], p.z as f32 + LIFT)
+                    };
+                    o.line(point(&pair[0]), point(&pair[1]), [0.0, 1.0, 1.0]);
+                }
+            }
+        }
+
+        if opts.locations {
+            for (location_type, locations) in map.locations.iter() {
+                let color = match *location_type {
+                    location::PoliceStation => [0.0, 0.0, 1.0],
+                    location::Hospital => [1.0, 1.0, 1.0],
+                    location::FireStation => [1.0, 0.0, 0.0],
+                    location::Unknown => [0.5, 0.5, 0.5]
+                };
+                for l in locations.iter() {
+                    let p = &l.position;
+                    let h = p.z as f32 + LIFT;
+                    o.rect([p.x as f32 + 0.25, p.y as f32 + 0.25], [0.5, 0.5], h, color);
+                    o.line(world([p.x as f32 + 0.25, p.y as f32 + 0.25], h),
+                           world([p.x as f32 + 0.75, p.y as f32 + 0.75], h), color);
+                    o.line(world([p.x as f32 + 0.75, p.y as f32 + 0.25], h),
+                           world([p.x as f32 + 0.25, p.y as f32 + 0.75], h), color);
+                }
+            }
+        }
+
+        if opts.objects {
+            for obj in map.objects.iter() {
+                let info = style.and_then(|s| s.object_info.as_slice().get(obj.object_type as uint));
+                let (w, d, h) = match info {
+                    Some(info) => (info.width as f32 / UNITS_PER_BLOCK,
+                                   info.height as f32 / UNITS_PER_BLOCK,
+                                   info.depth as f32 / UNITS_PER_BLOCK),
+                    None => (DEFAULT_OBJECT_SIZE, DEFAULT_OBJECT_SIZE, DEFAULT_OBJECT_SIZE)
+                };
+                let centre = [obj.x as f32 / UNITS_PER_BLOCK, obj.y as f32 / UNITS_PER_BLOCK];
+                let z = obj.z as f32 / UNITS_PER_BLOCK;
+                let yaw = obj.yaw as f32 / ANGLE_UNITS_PER_TURN * 2.0 * PI;
+                let (sin, cos) = yaw.sin_cos();
+                let corner = |dx: f32, dy: f32, z: f32| {
+                    world([centre[0] + dx * cos - dy * sin, centre[1] + dx * sin + dy * cos], z)
+                };
+                let (hw, hd) = (w / 2.0, d / 2.0);
+                for &z in [z + LIFT, z + h].iter() {
+                    let c = [corner(-hw, -hd, z), corner(hw, -hd, z),
+                             corner(hw, hd, z), corner(-hw, hd, z)];
+                    for i in range(0, 4) {
+                        o.line(c[i], c[(i + 1) % 4], [1.0, 0.0, 1.0]);
+                    }
+                }
+            }
+        }
+
+        Ok(o)
+    }
+
+    /// Adds an arrow from the centre of block `x`, `y` towards each
+    /// side traffic may leave by.
+    fn arrows(&mut self, x: uint, y: uint, h: f32, block: &Block) {
+        let c = [x as f32 + 0.5, y as f32 + 0.5];
+        let dirs = [(block.is_north_direction_allowed(), [0.0, -1.0]),
+                    (block.is_south_direction_allowed(), [0.0, 1.0]),
+                    (block.is_west_direction_allowed(), [-1.0, 0.0]),
+                    (block.is_east_direction_allowed(), [1.0, 0.0])];
+        for &(allowed, d) in dirs.iter() {
+            if !allowed {
+                continue;
+            }
+            let tip = [c[0] + d[0] * 0.4, c[1] + d[1] * 0.4];
+            let back = [tip[0] - d[0] * 0.15, tip[1] - d[1] * 0.15];
+            // Perpendicular to the arrow.
+            let side = [-d[1] * 0.1, d[0] * 0.1];
+            let color = [0.0, 1.0, 0.0];
+            self.line(world(c, h), world(tip, h), color);
+            self.line(world([back[0] + side[0], back[1] + side[1]], h), world(tip, h), color);
+            self.line(world([back[0] - side[0], back[1] - side[1]], h), world(tip, h), color);
+        }
+    }
+
+    /// Adds the outline of a map rectangle at height `h`.
+    fn rect(&mut self, pos: [f32, ..2], size: [f32, ..2], h: f32, color: [f32, ..3]) {
+        let (x0, y0, x1, y1) = (pos[0], pos[1], pos[0] + size[0], pos[1] + size[1]);
+        self.line(world([x0, y0], h), world([x1, y0], h), color);
+        self.line(world([x1, y0], h), world([x1, y1], h), color);
+        self.line(world([x1, y1], h), world([x0, y1], h), color);
+        self.line(world([x0, y1], h), world([x0, y0], h), color);
+    }
+
+    /// Adds a line between world points `a` and `b` as a flat strip
+    /// facing up.
+    fn line(&mut self, a: [f32, ..3], b: [f32, ..3], color: [f32, ..3]) {
+        let (dx, dz) = (b[0] - a[0], b[2] - a[2]);
+        let len = (dx * dx + dz * dz).sqrt();
+        let (sx, sz) = if len > 0.0 {
+            (-dz / len * LINE_WIDTH / 2.0, dx / len * LINE_WIDTH / 2.0)
+        } else {
+            (LINE_WIDTH / 2.0, 0.0)
+        };
+        let white = self.atlas.regions[0].clone();
+        let uv = [white.uv_min[0], white.uv_min[1]];
+        self.quad([[a[0] + sx, a[1], a[2] + sz], [a[0] - sx, a[1], a[2] - sz],
+                   [b[0] + sx, b[1], b[2] + sz], [b[0] - sx, b[1], b[2] - sz]],
+                  [uv, uv, uv, uv], color);
+    }
+
+    /// Adds the text of `region` with its top left corner at map
+    /// position `pos`.
+    fn label(&mut self, pos: [f32, ..2], h: f32, region: &Region) {
+        let w = region.width as f32 / TEXT_PIXELS_PER_BLOCK;
+        let d = region.height as f32 / TEXT_PIXELS_PER_BLOCK;
+        let (u0, v0, u1, v1) = (region.uv_min[0], region.uv_min[1],
+                                region.uv_max[0], region.uv_max[1]);
+        self.quad([world(pos, h), world([pos[0] + w, pos[1]], h),
+                   world([pos[0], pos[1] + d], h), world([pos[0] + w, pos[1] + d], h)],
+                  [[u0, v0], [u1, v0], [u0, v1], [u1, v1]],
+                  [1.0, 1.0, 1.0]);
+    }
+
+    /// Adds a quad with corners in the order top left, top right,
+    /// bottom left, bottom right.
+    fn quad(&mut self, corners: [[f32, ..3], ..4], uvs: [[f32, ..2], ..4], color: [f32, ..3]) {
+        let i = self.verts.len() as u32;
+        for (c, uv) in corners.iter().zip(uvs.iter()) {
+            self.verts.push(Vertex::new(*c, *uv, color));
+        }
+        self.indices.push_all([i, i + 1, i + 2, i + 1, i + 2, i + 3]);
+    }
+}
+
+/// Returns the world position of map position `pos` at height `h`.
+/// See `Chunk::from_map` for the world axes: block `x` spans world
+/// `256 - x` to `257 - x`.
+fn world(pos: [f32, ..2], h: f32) -> [f32, ..3] {
+    [257.0 - pos[0], h, pos[1]]
+}
+
+/// Returns the highest non-air block of column `x`, `y` and its level.
+fn top_block(map: &Map, x: uint, y: uint) -> Option<(uint, Block)> {
+    let column = &map.blocks[x][y];
+    range(0, column.len()).rev()
+        .find(|&z| column[z].get_block_type() != block::Air)
+        .map(|z| (z, column[z]))
+}
+
+/// Colours slopes by how many blocks they rise over.
+fn slope_color(slope: u8) -> [f32, ..3] {
+    match slope {
+        1 ... 8 => [1.0, 0.5, 0.0],
+        9 ... 40 => [1.0, 0.0, 0.0],
+        _ => [0.5, 0.0, 1.0]
+    }
+}
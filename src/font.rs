@@ -0,0 +1,183 @@
+use std::vec::Vec;
+use std::io::{File, IoResult};
+use piston::image::{GenericImage, ImageBuf, Rgba};
+
+use style::clut::vga_to_rgb;
+
+/// The character of the first glyph in a font file.
+static FIRST_CHAR: uint = 32;
+
+/// A bitmap font loaded from a `.fon` file.
+///
+/// The file starts with the number of glyphs and the glyph height,
+/// followed by the width and 8-bit pixels of each glyph. A 256
+/// colour palette closes the file.
+pub struct Font {
+    pub height: u8,
+    pub glyphs: Vec<Glyph>,
+    /// The palette stored in the font file.
+    pub palette: Vec<Rgba<u8>>
+}
+
+pub struct Glyph {
+    pub width: u8,
+    pub height: u8,
+    /// Colour indices, row by row. Index 0 is transparent.
+    pub pixels: Vec<u8>
+}
+
+impl Font {
+    pub fn from_file(filename: &str) -> IoResult<Font> {
+        println!("Loading font {}", filename);
+
+        let mut f = try!(File::open(&Path::new(filename)));
+        Font::read(&mut f)
+    }
+
+    /// Reads a font in `.fon` format from `f`.
+    pub fn read<R: Reader>(f: &mut R) -> IoResult<Font> {
+        let num_glyphs = try!(f.read_u8());
+        let height = try!(f.read_u8());
+
+        let mut glyphs = Vec::with_capacity(num_glyphs as uint);
+        for _ in range(0, num_glyphs) {
+            let width = try!(f.read_u8());
+            glyphs.push(Glyph {
+                width: width,
+                height: height,
+                pixels: try!(f.read_exact(width as uint * height as uint))
+            });
+        }
+
+        let mut palette = Vec::with_capacity(256);
+        for _ in range(0, 256u) {
+            palette.push(Rgba(vga_to_rgb(try!(f.read_u8())),
+                              vga_to_rgb(try!(f.read_u8())),
+                              vga_to_rgb(try!(f.read_u8())),
+                              255));
+        }
+
+        Ok(Font {
+            height: height,
+            glyphs: glyphs,
+            palette: palette
+        })
+    }
+
+    /// Returns the glyph of `c`, if the font has one.
+    pub fn glyph(&self, c: char) -> Option<&Glyph> {
+        let c = c as uint;
+        if c < FIRST_CHAR {
+            return None;
+        }
+        self.glyphs.as_slice().get(c - FIRST_CHAR)
+    }
+
+    /// Returns the width of `text` in pixels. Characters without a
+    /// glyph advance by the width of a space.
+    pub fn text_width(&self, text: &str) -> uint {
+        let space = self.glyph(' ').map_or(0, |g| g.advance());
+        text.chars().fold(0, |width, c| {
+            width + self.glyph(c).map_or(space, |g| g.advance())
+        })
+    }
+
+    /// Returns `text` on one line coloured with `palette`. Characters
+    /// without a glyph are left blank.
+    pub fn render_text(&self, text: &str, palette: &[Rgba<u8>]) -> ImageBuf<Rgba<u8>> {
+        let width = self.text_width(text);
+        let mut img = ImageBuf::from_pixel(width as u32, self.height as u32, Rgba(0, 0, 0, 0));
+        let space = self.glyph(' ').map_or(0, |g| g.advance());
+        let mut x0 = 0;
+        for c in text.chars() {
+            match self.glyph(c) {
+                Some(g) => {
+                    let glyph = g.to_image(palette);
+                    for y in range(0, g.height as u32) {
+                        for x in range(0, g.width as u32) {
+                            img.put_pixel(x0 + x, y, glyph.get_pixel(x, y));
+                        }
+                    }
+                    x0 += g.advance() as u32;
+                },
+                None => x0 += space as u32
+            }
+        }
+        img
+    }
+}
+
+impl Glyph {
+    /// Returns the horizontal advance of the glyph in pixels.
+    pub fn advance(&self) -> uint {
+        self.width as uint
+    }
+
+    /// Returns the glyph coloured with `palette`. Colour indices past
+    /// the end of `palette` are transparent.
+    pub fn to_image(&self, palette: &[Rgba<u8>]) -> ImageBuf<Rgba<u8>> {
+        let width = self.width as uint;
+        ImageBuf::from_fn(self.width as u32, self.height as u32, |x, y| {
+            let index = self.pixels[y as uint * width + x as uint];
+            if index == 0 {
+                Rgba(0, 0, 0, 0)
+            } else {
+                palette.get(index as uint).map_or(Rgba(0, 0, 0, 0), |c| *c)
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{MemReader, MemWriter};
+    use piston::image::{GenericImage, Rgba};
+
+    use super::Font;
+
+    /// Returns a font of two glyphs two pixels high: a blank space one
+    /// pixel wide and a `!` three pixels wide.
+    fn fon() -> Vec<u8> {
+        let mut w = MemWriter::new();
+        w.write(&[2, 2]).unwrap();
+        w.write(&[1, 0, 0]).unwrap();
+        w.write(&[3, 0, 1, 0, 0, 2, 0]).unwrap();
+        // Colour 1 is full red and half blue, colour 2 is white.
+        for n in range(0, 256u) {
+            match n {
+                1 => w.write(&[63, 0, 32]).unwrap(),
+                2 => w.write(&[63, 63, 63]).unwrap(),
+                _ => w.write(&[0, 0, 0]).unwrap()
+            }
+        }
+        w.unwrap()
+    }
+
+    #[test]
+    fn reads_glyphs_and_palette() {
+        let font = Font::read(&mut MemReader::new(fon())).unwrap();
+        assert_eq!(font.height, 2);
+        assert_eq!(font.glyphs.len(), 2);
+        assert_eq!(font.glyph(' ').map(|g| g.width), Some(1));
+        assert_eq!(font.glyph('!').map(|g| g.pixels.clone()), Some(vec!(0, 1, 0, 0, 2, 0)));
+        assert!(font.glyph('"').is_none());
+        // Characters without a glyph advance like a space.
+        assert_eq!(font.text_width("! \""), 5);
+
+        let Rgba(r, g, b, a) = font.palette[1];
+        assert_eq!((r, g, b, a), (255, 0, 130, 255));
+
+        let img = font.glyphs[1].to_image(font.palette.as_slice());
+        let Rgba(_, _, _, a) = img.get_pixel(0, 0);
+        assert_eq!(a, 0);
+        let Rgba(r, g, b, a) = img.get_pixel(1, 1);
+        assert_eq!((r, g, b, a), (255, 255, 255, 255));
+    }
+
+    #[test]
+    fn fails_on_a_truncated_file() {
+        let mut data = fon();
+        data.truncate(100);
+        assert!(Font::read(&mut MemReader::new(data)).is_err());
+    }
+}
@@ -0,0 +1,14 @@
+use std::io::File;
+use serialize::Decodable;
+use serialize::json;
+
+/// Reads and decodes the JSON file at `path`.
+pub fn load<T: Decodable<json::Decoder, json::DecoderError>>(path: &Path) -> Result<T, String> {
+    let s = match File::open(path).read_to_string() {
+        Ok(s) => s,
+        Err(why) => return Err(format!("Could not read {}: {}", path.display(), why))
+    };
+    json::decode(s.as_slice()).map_err(|why| {
+        format!("Could not parse {}: {}", path.display(), why)
+    })
+}
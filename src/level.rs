@@ -0,0 +1,43 @@
+use style;
+
+/// The cities of the original game.
+#[deriving(Show, PartialEq)]
+pub enum Level {
+    NewYork,
+    SanAndreas,
+    ViceCity
+}
+
+impl Level {
+    /// Parses a level name as given on the command line, e.g. `nyc`
+    /// or `vice-city`.
+    pub fn from_name(name: &str) -> Option<Level> {
+        match name {
+            "nyc" | "new-york" | "liberty-city" | "1" => Some(NewYork),
+            "sanb" | "san-andreas" | "2" => Some(SanAndreas),
+            "miami" | "vice-city" | "3" => Some(ViceCity),
+            _ => None
+        }
+    }
+
+    /// Returns the map file of the level.
+    pub fn map_file(&self) -> &'static str {
+        match *self {
+            NewYork => "nyc.cmp",
+            SanAndreas => "sanb.cmp",
+            ViceCity => "miami.cmp"
+        }
+    }
+
+    /// Returns the style file of the level in `format`.
+    pub fn style_file(&self, format: style::Format) -> &'static str {
+        match (*self, format) {
+            (NewYork, style::G24) => "style001.g24",
+            (NewYork, style::Gry) => "style001.gry",
+            (SanAndreas, style::G24) => "style002.g24",
+            (SanAndreas, style::Gry) => "style002.gry",
+            (ViceCity, style::G24) => "style003.g24",
+            (ViceCity, style::Gry) => "style003.gry"
+        }
+    }
+}
@@ -0,0 +1,27 @@
+#![feature(phase, unsafe_destructor)]
+
+extern crate serialize;
+extern crate getopts;
+extern crate piston;
+extern crate cam;
+extern crate gfx;
+extern crate device;
+extern crate render;
+extern crate gl;
+
+pub use self::tile_set::TileSet;
+
+pub mod style;
+pub mod map;
+pub mod renderer;
+pub mod chunk;
+pub mod tile_set;
+pub mod font;
+pub mod assets;
+pub mod json_file;
+pub mod config;
+pub mod level;
+pub mod minimap;
+pub mod camera;
+pub mod lighting;
+pub mod debug_overlay;
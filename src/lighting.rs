@@ -0,0 +1,287 @@
+use std::cmp::Equal;
+use std::num::Float;
+
+use map::Map;
+use map::object::UNITS_PER_BLOCK;
+
+/// The number of point lights the block shader takes.
+pub static MAX_LIGHTS: uint = 4;
+
+/// The length of a game day in real seconds.
+pub static DAY_LENGTH: f32 = 600.0;
+
+/// How long an explosion lights its surroundings, in seconds.
+static EXPLOSION_TIME: f32 = 1.5;
+
+/// How the world is lit.
+#[deriving(Clone, PartialEq, Show)]
+pub enum LightingPreset {
+    /// Full brightness all day without fog or lights, like the
+    /// original game.
+    Classic,
+    /// Ambient light and fog follow the time of day, lamps light up
+    /// at night.
+    DayNight
+}
+
+impl LightingPreset {
+    pub fn from_name(name: &str) -> Option<LightingPreset> {
+        match name {
+            "classic" => Some(Classic),
+            "day-night" => Some(DayNight),
+            _ => None
+        }
+    }
+}
+
+/// A light fading out linearly to nothing at `radius` blocks.
+#[deriving(Clone, Show)]
+pub struct PointLight {
+    pub pos: [f32, ..3],
+    pub color: [f32, ..3],
+    pub radius: f32,
+    pub intensity: f32
+}
+
+/// The time of day in hours, from 0 up to 24.
+#[deriving(Clone, Show)]
+pub struct TimeOfDay {
+    pub hours: f32
+}
+
+/// Ambient light and fog colours through the day, as (hour, ambient,
+/// fog). Colours in between are interpolated.
+static SKY: [(f32, [f32, ..3], [f32, ..3]), ..6] = [
+    (0.0, [0.15, 0.15, 0.3], [0.02, 0.02, 0.06]),
+    (5.0, [0.2, 0.2, 0.35], [0.05, 0.05, 0.1]),
+    (7.0, [0.9, 0.7, 0.6], [0.7, 0.5, 0.4]),
+    (12.0, [1.0, 1.0, 1.0], [0.6, 0.7, 0.8]),
+    (18.0, [0.95, 0.75, 0.6], [0.7, 0.45, 0.35]),
+    (21.0, [0.2, 0.2, 0.35], [0.05, 0.05, 0.1])
+];
+
+impl TimeOfDay {
+    pub fn new(hours: f32) -> TimeOfDay {
+        TimeOfDay { hours: hours % 24.0 }
+    }
+
+    /// Moves the clock on by `dt` real seconds.
+    pub fn advance(&mut self, dt: f32) {
+        self.hours = (self.hours + dt * 24.0 / DAY_LENGTH) % 24.0;
+    }
+
+    pub fn ambient(&self) -> [f32, ..3] {
+        let (ambient, _) = self.sky();
+        ambient
+    }
+
+    pub fn fog_color(&self) -> [f32, ..3] {
+        let (_, fog) = self.sky();
+        fog
+    }
+
+    /// How dark it is, from 0 at noon to 1 in the dead of night.
+    pub fn darkness(&self) -> f32 {
+        let a = self.ambient();
+        1.0 - (a[0] + a[1] + a[2]) / 3.0
+    }
+
+    fn sky(&self) -> ([f32, ..3], [f32, ..3]) {
+        let n = SKY.len();
+        let next = SKY.iter().position(|&(h, _, _)| h > self.hours).unwrap_or(n);
+        let (h0, a0, f0) = SKY[(next + n - 1) % n];
+        let (h1, a1, f1) = SKY[next % n];
+        // The last key wraps around to the first one at midnight.
+        let h1 = if h1 <= h0 { h1 + 24.0 } else { h1 };
+        let t = (self.hours - h0) / (h1 - h0);
+        (lerp(a0, a1, t), lerp(f0, f1, t))
+    }
+}
+
+/// The lighting uniforms of the block shader.
+#[deriving(Clone, Show)]
+pub struct LightUniforms {
+    pub ambient: [f32, ..3],
+    pub fog_color: [f32, ..3],
+    pub fog_density: f32,
+    /// One light per column: position and radius.
+    pub light_pos: [[f32, ..4], ..4],
+    /// One light per column: colour and intensity.
+    pub light_color: [[f32, ..4], ..4]
+}
+
+impl LightUniforms {
+    /// Full brightness without fog or lights.
+    pub fn flat() -> LightUniforms {
+        LightUniforms {
+            ambient: [1.0, 1.0, 1.0],
+            fog_color: [0.0, 0.0, 0.0],
+            fog_density: 0.0,
+            light_pos: [[0.0, ..4], ..4],
+            light_color: [[0.0, ..4], ..4]
+        }
+    }
+}
+
+/// An explosion, fading out over `EXPLOSION_TIME`.
+struct Explosion {
+    pos: [f32, ..3],
+    age: f32
+}
+
+/// The time of day and the lights of the world.
+pub struct Lighting {
+    pub preset: LightingPreset,
+    pub time: TimeOfDay,
+    /// Street lamps, lit at night.
+    pub lamps: Vec<PointLight>,
+    /// Lights that only last a frame, such as headlights. Cleared by
+    /// `begin_frame`.
+    pub frame_lights: Vec<PointLight>,
+    explosions: Vec<Explosion>,
+    /// How thick the fog is at night, per block.
+    pub fog_density: f32
+}
+
+impl Lighting {
+    pub fn new(preset: LightingPreset) -> Lighting {
+        Lighting {
+            preset: preset,
+            time: TimeOfDay::new(12.0),
+            lamps: Vec::new(),
+            frame_lights: Vec::new(),
+            explosions: Vec::new(),
+            fog_density: 0.02
+        }
+    }
+
+    /// Places a lamp above every object of `map` whose type is in
+    /// `lamp_types`.
+    pub fn add_lamps(&mut self, map: &Map, lamp_types: &[u8]) {
+        for obj in map.objects.iter() {
+            if !lamp_types.contains(&obj.object_type) {
+                continue;
+            }
+            let x = obj.x as f32 / UNITS_PER_BLOCK;
+            let y = obj.y as f32 / UNITS_PER_BLOCK;
+            let z = obj.z as f32 / UNITS_PER_BLOCK;
+            self.lamps.push(PointLight {
+                // See `Chunk::from_map` for the world axes.
+                pos: [257.0 - x, z + 1.0, y],
+                color: [1.0, 0.85, 0.6],
+                radius: 4.0,
+                intensity: 1.0
+            });
+        }
+    }
+
+    /// Lights up the road in front of a car at `pos` heading `heading`
+    /// radians, for this frame.
+    pub fn add_headlights(&mut self, pos: [f32, ..3], heading: f32) {
+        let (sin, cos) = heading.sin_cos();
+        self.frame_lights.push(PointLight {
+            pos: [pos[0] + sin * 1.5, pos[1] + 0.5, pos[2] - cos * 1.5],
+            color: [1.0, 1.0, 0.85],
+            radius: 3.0,
+            intensity: 1.0
+        });
+    }
+
+    pub fn add_explosion(&mut self, pos: [f32, ..3]) {
+        self.explosions.push(Explosion { pos: pos, age: 0.0 });
+    }
+
+    /// Clears the frame lights, before the lights of the next frame
+    /// are added.
+    pub fn begin_frame(&mut self) {
+        self.frame_lights.clear();
+    }
+
+    /// Advances the clock and the explosions by `dt` seconds.
+    pub fn update(&mut self, dt: f32) {
+        if self.preset == DayNight {
+            self.time.advance(dt);
+        }
+        for e in self.explosions.iter_mut() {
+            e.age += dt;
+        }
+        self.explosions.retain(|e| e.age < EXPLOSION_TIME);
+    }
+
+    /// Returns the shader uniforms, with the `MAX_LIGHTS` lights
+    /// nearest to `eye`.
+    pub fn uniforms(&self, eye: [f32, ..3]) -> LightUniforms {
+        if self.preset == Classic {
+            return LightUniforms::flat();
+        }
+
+        let darkness = self.time.darkness();
+        let mut lights: Vec<PointLight> = self.frame_lights.clone();
+        for e in self.explosions.iter() {
+            lights.push(PointLight {
+                pos: e.pos,
+                color: [1.0, 0.6, 0.2],
+                radius: 8.0,
+                intensity: 2.0 * (1.0 - e.age / EXPLOSION_TIME)
+            });
+        }
+        for lamp in self.lamps.iter() {
+            let mut lamp = lamp.clone();
+            lamp.intensity *= darkness;
+            lights.push(lamp);
+        }
+
+        let dist = |l: &PointLight| {
+            let d = [l.pos[0] - eye[0], l.pos[1] - eye[1], l.pos[2] - eye[2]];
+            d[0] * d[0] + d[1] * d[1] + d[2] * d[2]
+        };
+        lights.retain(|l| l.intensity > 0.0);
+        // Lights at NaN positions are left wherever the sort puts them.
+        lights.sort_by(|a, b| dist(a).partial_cmp(&dist(b)).unwrap_or(Equal));
+
+        let mut uniforms = LightUniforms::flat();
+        uniforms.ambient = self.time.ambient();
+        uniforms.fog_color = self.time.fog_color();
+        uniforms.fog_density = self.fog_density * darkness;
+        for (i, l) in lights.iter().take(MAX_LIGHTS).enumerate() {
+            uniforms.light_pos[i] = [l.pos[0], l.pos[1], l.pos[2], l.radius];
+            uniforms.light_color[i] = [l.color[0], l.color[1], l.color[2], l.intensity];
+        }
+        uniforms
+    }
+}
+
+fn lerp(a: [f32, ..3], b: [f32, ..3], t: f32) -> [f32, ..3] {
+    [a[0] + (b[0] - a[0]) * t,
+     a[1] + (b[1] - a[1]) * t,
+     a[2] + (b[2] - a[2]) * t]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::Float;
+
+    use super::TimeOfDay;
+
+    fn assert_near(a: [f32, ..3], b: [f32, ..3]) {
+        for i in range(0, 3u) {
+            assert!((a[i] - b[i]).abs() < 1e-3, "{} != {}", a, b);
+        }
+    }
+
+    #[test]
+    fn sky_wraps_around_midnight() {
+        // Halfway between the 21:00 and midnight keys.
+        assert_near(TimeOfDay::new(22.5).ambient(), [0.175, 0.175, 0.325]);
+        assert_near(TimeOfDay::new(22.5).fog_color(), [0.035, 0.035, 0.08]);
+        // Just before midnight, next to the midnight key.
+        assert_near(TimeOfDay::new(23.999).ambient(), [0.15, 0.15, 0.3]);
+        assert_near(TimeOfDay::new(0.0).ambient(), [0.15, 0.15, 0.3]);
+    }
+
+    #[test]
+    fn sky_matches_its_keys() {
+        assert_near(TimeOfDay::new(12.0).ambient(), [1.0, 1.0, 1.0]);
+        assert_near(TimeOfDay::new(24.0).ambient(), [0.15, 0.15, 0.3]);
+    }
+}
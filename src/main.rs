@@ -10,77 +10,154 @@ extern crate sdl2_window;
 extern crate gfx;
 extern crate device;
 extern crate render;
-
+extern crate gl;
+extern crate gta;
 
 use std::cell::RefCell;
+use std::io;
+use std::io::fs;
+use std::io::fs::PathExtensions;
+use std::num::Float;
 
 use sdl2_window::Sdl2Window;
 use gfx::{Device, DeviceHelper};
-use piston::image;
 use event::{ Events, WindowSettings };
 use event::window::CaptureCursor;
 use current::{ Set };
 
-use map::{Map, block, block_data};
-use style::{Style};
-use renderer::{Renderer, Texture, Vertex, Params, _ParamsLink};
-use renderer::program::Program;
-use renderer::buffer::Buffer;
-use chunk::Chunk;
+use gta::map::{Map, block, block_data};
+use gta::style::{Style};
+use gta::renderer::{Renderer, Texture, Vertex, Params, _ParamsLink, Opaque, Transparent, Overlay};
+use gta::renderer::buffer::Buffer;
+use gta::renderer::capture;
+use gta::renderer::sprite_batch::{SpriteAtlas, SpriteBatch, Sprite};
+use gta::renderer::tile_atlas::TileAtlas;
+use gta::chunk::Chunk;
+use gta::assets;
+use gta::assets::Assets;
+use gta::config::{Config, Settings};
+use gta::style;
+use gta::style::sprite_numbers;
+use gta::camera::{TopDown, TopDownSettings};
+use gta::lighting::Lighting;
+use gta::font::Font;
+use gta::debug_overlay::{DebugOverlay, OverlayOptions};
+
+/// The cameras that can be switched between with C.
+enum CameraMode {
+    FreeFly,
+    Chase
+}
+
+/// The speed of the chase camera's target in blocks per second.
+static TARGET_SPEED: f32 = 10.0;
+
+/// The most sprites drawn in a frame.
+static MAX_SPRITES: uint = 4096;
+
+/// How often shader sources are checked for changes, in seconds.
+static SHADER_POLL_INTERVAL: f32 = 0.5;
+
+/// The font used to label the debug overlay.
+static FONT_FILE: &'static str = "font1.fon";
+
+/// The time step between captured frames, in seconds.
+static CAPTURE_DT: f32 = 1.0 / 30.0;
 
-pub use self::tile_set::TileSet;
+/// The size of the window while capturing. Frames are drawn offscreen,
+/// so it only holds the GL context.
+static CAPTURE_WINDOW_SIZE: u32 = 64;
 
-mod style;
-mod map;
-mod renderer;
-mod chunk;
-mod tile_set;
+/// Returns the first `screenshot-NNNN.png` in the working directory
+/// that doesn't exist yet.
+fn screenshot_path() -> Path {
+    let mut n = 0u;
+    loop {
+        let path = Path::new(format!("screenshot-{:04}.png", n));
+        if !path.exists() {
+            return path;
+        }
+        n += 1;
+    }
+}
+
+/// Returns the projection matrix for a `width` by `height` view.
+fn perspective(settings: &Settings, width: u32, height: u32) -> [[f32, ..4], ..4] {
+    cam::CameraPerspective {
+        fov: settings.fov,
+        near_clip: 0.1,
+        far_clip: 1000.0,
+        aspect_ratio: (width as f32) / (height as f32)
+    }.projection()
+}
 
 fn main() {
-    let (width, height) = (1920, 1080);
+    let config = match Config::from_args(std::os::args().as_slice()) {
+        Err(why) => panic!("{}", why),
+        Ok(None) => return,
+        Ok(Some(config)) => config
+    };
+    let settings = config.settings.clone();
+    let capturing = config.capture.is_some();
+
+    let (width, height) = (settings.width, settings.height);
+    let window_size = if capturing {
+        [CAPTURE_WINDOW_SIZE, CAPTURE_WINDOW_SIZE]
+    } else {
+        [width, height]
+    };
     let mut window = Sdl2Window::new(
         piston::shader_version::opengl::OpenGL_3_2,
         piston::WindowSettings {
             title: "gta".to_string(),
-            size: [width, height],
-            fullscreen: true,
+            size: window_size,
+            fullscreen: settings.fullscreen && !capturing,
             exit_on_esc: true,
-            samples: 4
+            samples: settings.samples
         }
     );
-    window.set_mut(CaptureCursor(true));
+    if !capturing {
+        window.set_mut(CaptureCursor(true));
+    }
+    sdl2::video::gl_set_swap_interval(if settings.vsync && !capturing { 1 } else { 0 });
 
-    let map = match Map::from_file("data/nyc.cmp") {
-        Err(why) => panic!("Could not load map: {}", why.desc),
+    let mut assets = Assets::new(config.data_dir.clone());
+    assets.add_mods_in(&config.data_dir.join("mods"));
+    for dir in config.mods.iter() {
+        assets.add_mod(dir.clone());
+    }
+
+    let map = match assets.load_map(config.level.map_file()) {
+        Err(why) => panic!("Could not load map: {}", why),
         Ok(map) => map
     };
-    let style = match Style::from_file("data/style001.g24") {
-        Err(why) => panic!("Could not load style: {}", why.desc),
+    // Prefer the 24-bit style, the DOS version only ships 8-bit ones.
+    let style_file = if assets.exists(config.level.style_file(style::G24)) {
+        config.level.style_file(style::G24)
+    } else {
+        config.level.style_file(style::Gry)
+    };
+    let style = match assets.load_style(style_file) {
+        Err(why) => panic!("Could not load style: {}", why),
         Ok(style) => style
     };
 
-    let image = image::ImageLuma8(style.tiles.buffer);
-    let fout = std::io::File::create(&Path::new("test.png")).unwrap();
-    let _ = image.save(fout, image::PNG);
-
-    let (vertex_data, index_data) = match Chunk::from_map(&map, [0, 0]) {
-        Some(chunk) => (chunk.verts, chunk.indices),
-        None => panic!("Couldn't generate chunk from map!")
+    let tiles = match TileAtlas::from_style(&style) {
+        Err(why) => panic!("Could not build tile atlas: {}", why),
+        Ok(tiles) => tiles
     };
+    let chunks = Chunk::all_from_map(&map, Some(&tiles));
 
     let mut device = gfx::GlDevice::new(|s| unsafe {
         std::mem::transmute(sdl2::video::gl_get_proc_address(s))
     });
+    // For reading back frames, which gfx doesn't do.
+    gl::load_with(|s| unsafe {
+        std::mem::transmute(sdl2::video::gl_get_proc_address(s))
+    });
     let frame = gfx::Frame::new(width as u16, height as u16);
 
-    let projection = cam::CameraPerspective {
-        fov: 90.0f32,
-        near_clip: 0.1,
-        far_clip: 1000.0,
-        aspect_ratio: {
-            (width as f32) / (height as f32)
-        }
-    }.projection();
+    let mut projection = perspective(&settings, width, height);
 
     let mut first_person_settings = cam::FirstPersonSettings::keyboard_wasd();
     first_person_settings.speed_horizontal = 12.0;
@@ -90,36 +167,261 @@ fn main() {
         first_person_settings
     );
 
-    let texture = Texture::from_file(&Path::new("data/texture.png"), &mut device).unwrap();
-    let sam = device.create_sampler(gfx::tex::SamplerInfo::new(gfx::tex::Scale, gfx::tex::Tile));
+    // Until there is a player, the chase camera follows a point
+    // moved with the arrow keys.
+    let mut target = [128.0f32, 2.0, 128.0];
+    let mut target_dir = [0.0f32, 0.0];
+    let mut target_heading = 0.0f32;
+    let mut top_down = TopDown::new(target, TopDownSettings::default());
+    let mut mode = FreeFly;
+    let mut shader_poll = 0.0f32;
+
+    let mut lighting = Lighting::new(settings.lighting);
+    let lamp_objects = config.lamp_objects.clone()
+        .unwrap_or_else(|| style.lamp_objects());
+    lighting.add_lamps(&map, lamp_objects.as_slice());
 
-    let mut renderer = Renderer::new(device, frame);
+    let tile_texture = Texture::from_rgba8(tiles.image.clone(), &mut device);
+    let sam = device.create_sampler(gfx::tex::SamplerInfo::new(gfx::tex::Scale, gfx::tex::Clamp));
 
-    let prog: Program = Program::new(&mut renderer, "shader");
-    let buf: Buffer<Vertex, Params, _ParamsLink> = Buffer::new(
+    let max_texture_size = device.get_capabilities().max_texture_size as u32;
+    let atlas = match SpriteAtlas::from_style(&style, max_texture_size) {
+        Err(why) => panic!("Could not build sprite atlas: {}", why),
+        Ok(atlas) => atlas
+    };
+    let atlas_texture = Texture::from_rgba8(atlas.image.clone(), &mut device);
+    // Until there are vehicles, the chase target is drawn as the
+    // first car of the style.
+    let target_sprite = style.car_info.as_slice().head().and_then(|car| {
+        style.sprite(sprite_numbers::Car, car.sprite_number as uint).ok()
+    });
+
+    let mut renderer = Renderer::new(device, frame, assets.clone());
+    // Captures are drawn offscreen at the configured resolution and
+    // advance by a fixed step per frame, ignoring input, window size
+    // and shader edits, so runs are repeatable.
+    let mut captured = 0u;
+    match config.capture {
+        Some(_) => {
+            match fs::mkdir_recursive(&config.capture_dir, io::USER_RWX) {
+                Err(why) => panic!("Could not create {}: {}", config.capture_dir.display(), why),
+                Ok(()) => {}
+            }
+            renderer.draw_offscreen(width as u16, height as u16);
+        },
+        None => {}
+    }
+    let mut take_screenshot = false;
+
+    // See-through faces of flat blocks are alpha tested, so they are
+    // drawn in the opaque pass too, writing depth, after the other
+    // faces. Those draw colour 0 opaque.
+    for (i, chunk) in chunks.iter().enumerate() {
+        for &(indices, see_through) in [(&chunk.indices, false),
+                                        (&chunk.transparent_indices, true)].iter() {
+            if indices.is_empty() {
+                continue;
+            }
+            let buf: Buffer<Vertex, Params, _ParamsLink> = match Buffer::new(
+                &mut renderer,
+                "shader",
+                chunk.verts.as_slice(),
+                indices.as_slice(),
+                Opaque
+            ) {
+                Err(why) => panic!("{}", why),
+                Ok(buf) => buf
+            };
+            let id = renderer.add(buf, Opaque, (tile_texture.handle.clone(), Some(sam.clone())));
+            renderer.set_order(id, if see_through { 1 } else { 0 });
+            renderer.set_alpha_test(id, see_through);
+            renderer.set_chunk(id, i);
+            match chunk.bounds {
+                Some(bounds) => renderer.set_bounds(id, bounds),
+                None => {}
+            }
+        }
+    }
+
+    let mut sprites = SpriteBatch::new(MAX_SPRITES);
+    let sprite_buf: Buffer<Vertex, Params, _ParamsLink> = match Buffer::dynamic(
         &mut renderer,
-        &prog,
-        vertex_data.as_slice(),
-        index_data.as_slice()
-    );
+        "shader",
+        sprites.vertices(&atlas, [0.0, 0.0, 0.0]).as_slice(),
+        sprites.indices().as_slice(),
+        Transparent
+    ) {
+        Err(why) => panic!("{}", why),
+        Ok(buf) => buf
+    };
+    let sprite_id = renderer.add(sprite_buf, Transparent, (atlas_texture.handle, Some(sam.clone())));
 
-    let mut data = renderer::Params {
-        projection: projection,
-        view: first_person.camera(0.0).orthogonal(),
-        texture: (texture.handle, Some(sam))
+    // The debug overlay names zones with the first font of the level,
+    // if it is there.
+    let font = if assets.exists(FONT_FILE) {
+        let path = assets.resolve(FONT_FILE);
+        let font = assets::path_str(&path).and_then(|path| {
+            Font::from_file(path).map_err(|why| format!("{}", why))
+        });
+        match font {
+            Err(why) => { println!("Could not load font: {}", why); None },
+            Ok(font) => Some(font)
+        }
+    } else {
+        None
+    };
+    let font_palette = font.as_ref().map(|f| style.font_palette(0).unwrap_or(f.palette.clone()));
+    let overlay_font = match (font.as_ref(), font_palette.as_ref()) {
+        (Some(f), Some(p)) => Some((f, p.as_slice())),
+        _ => None
+    };
+    let overlay = match DebugOverlay::build(&map, Some(&style), overlay_font,
+                                            &OverlayOptions::default(), max_texture_size) {
+        Err(why) => panic!("Could not build debug overlay: {}", why),
+        Ok(overlay) => overlay
     };
+    let overlay_texture = Texture::from_rgba8(overlay.atlas.image.clone(), &mut renderer.graphics.device);
+    let overlay_buf: Buffer<Vertex, Params, _ParamsLink> = match Buffer::new(
+        &mut renderer,
+        "overlay",
+        overlay.verts.as_slice(),
+        overlay.indices.as_slice(),
+        Overlay
+    ) {
+        Err(why) => panic!("{}", why),
+        Ok(buf) => buf
+    };
+    let overlay_id = renderer.add(overlay_buf, Overlay, (overlay_texture.handle, Some(sam.clone())));
+    let mut show_overlay = false;
+    renderer.set_visible(overlay_id, show_overlay);
 
     let window = RefCell::new(window);
     for e in Events::new(&window) {
-        use event::RenderEvent;
-        
-        first_person.event(&e);
+        use event::{RenderEvent, UpdateEvent, PressEvent, ReleaseEvent, ResizeEvent};
+        use input::{Keyboard, keyboard};
+
+        // Captures ignore input and the window's size.
+        if !capturing {
+            e.press(|button| {
+                match button {
+                    Keyboard(keyboard::C) => {
+                        mode = match mode {
+                            FreeFly => Chase,
+                            Chase => FreeFly
+                        };
+                    },
+                    Keyboard(keyboard::P) => {
+                        let stats = renderer.stats();
+                        println!("{} chunks drawn, {} culled", stats.drawn, stats.culled);
+                    },
+                    Keyboard(keyboard::X) => lighting.add_explosion(target),
+                    Keyboard(keyboard::F12) => take_screenshot = true,
+                    Keyboard(keyboard::F3) => {
+                        show_overlay = !show_overlay;
+                        renderer.set_visible(overlay_id, show_overlay);
+                    },
+                    Keyboard(keyboard::Up) => target_dir[1] = -1.0,
+                    Keyboard(keyboard::Down) => target_dir[1] = 1.0,
+                    Keyboard(keyboard::Left) => target_dir[0] = 1.0,
+                    Keyboard(keyboard::Right) => target_dir[0] = -1.0,
+                    _ => {}
+                }
+            });
+            e.release(|button| {
+                match button {
+                    Keyboard(keyboard::Up) | Keyboard(keyboard::Down) => target_dir[1] = 0.0,
+                    Keyboard(keyboard::Left) | Keyboard(keyboard::Right) => target_dir[0] = 0.0,
+                    _ => {}
+                }
+            });
+            e.resize(|w, h| {
+                projection = perspective(&settings, w, h);
+                renderer.resize(w as u16, h as u16);
+            });
+        }
+        let mut step = None;
+        e.update(|args| {
+            if !capturing {
+                step = Some(args.dt as f32);
+            }
+        });
+        e.render(|_| {
+            if capturing {
+                step = Some(CAPTURE_DT);
+            }
+        });
+        match step {
+            Some(dt) => {
+                target[0] += target_dir[0] * TARGET_SPEED * dt;
+                target[2] += target_dir[1] * TARGET_SPEED * dt;
+                if target_dir[0] != 0.0 || target_dir[1] != 0.0 {
+                    target_heading = target_dir[0].atan2(-target_dir[1]);
+                }
+                top_down.follow(target, dt);
+                lighting.update(dt);
+
+                shader_poll += dt;
+                if !capturing && shader_poll >= SHADER_POLL_INTERVAL {
+                    shader_poll = 0.0;
+                    for why in renderer.reload_programs().iter() {
+                        println!("{}", why);
+                    }
+                }
+            },
+            None => {}
+        }
+
+        match mode {
+            FreeFly if !capturing => first_person.event(&e),
+            _ => {}
+        }
         e.render(|args| {
-            data.view = first_person.camera(0.0).orthogonal();
+            let camera = match mode {
+                FreeFly => first_person.camera(0.0),
+                Chase => top_down.camera()
+            };
+
+            sprites.clear();
+            match target_sprite {
+                Some(region) => sprites.push(Sprite {
+                    pos: target,
+                    rotation: target_heading,
+                    scale: 1.0,
+                    region: region
+                }),
+                None => {}
+            }
+            renderer.update(sprite_id, sprites.vertices(&atlas, camera.position).as_slice());
 
+            lighting.begin_frame();
+            lighting.add_headlights(target, target_heading);
+            renderer.set_lighting(lighting.uniforms(camera.position));
+            renderer.set_camera(projection, camera.orthogonal());
             renderer.clear();
-            renderer.render(buf, data);
+            renderer.draw();
             renderer.end_frame();
+
+            if take_screenshot {
+                take_screenshot = false;
+                let path = screenshot_path();
+                match capture::save_png(renderer.screenshot(), &path) {
+                    Err(why) => println!("Could not save screenshot: {}", why),
+                    Ok(()) => println!("Saved {}", path.display())
+                }
+            }
+            if capturing {
+                let path = config.capture_dir.join(format!("frame-{:04}.png", captured));
+                match capture::save_png(renderer.screenshot(), &path) {
+                    Err(why) => panic!("Could not save frame: {}", why),
+                    Ok(()) => {}
+                }
+                captured += 1;
+            }
         });
+
+        match config.capture {
+            Some(n) if captured >= n => break,
+            _ => {}
+        }
     }
 }
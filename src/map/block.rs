@@ -1,3 +1,4 @@
+#[deriving(Encodable, Decodable)]
 pub struct Block {
     pub type_map: u16,
     pub type_map_ext: u8,
@@ -99,10 +100,13 @@ impl Block {
             self.type_map_ext & 1 != 0
     }
 
-    pub fn get_remap_index(&self) -> bool {
+    /// Returns the lid remap (0-3), which selects one of the four
+    /// palettes of the lid tile. Higher remaps are darker lighting
+    /// levels.
+    pub fn get_remap_index(&self) -> u8 {
         let first_cond: u8 = if self.type_map_ext & 8 != 0 { 1 } else { 0 };
         let second_cond: u8 = if self.type_map_ext & 16 != 0 { 2 } else { 0 };
-        first_cond + second_cond != 0
+        first_cond + second_cond
     }
 
     pub fn should_flip_north_south(&self) -> bool {
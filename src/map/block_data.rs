@@ -1,12 +1,28 @@
 use std::vec::Vec;
-use std::iter::range_step;
 
 use super::super::renderer::{Vertex};
+use super::super::renderer::tile_atlas::TileAtlas;
 use super::block;
 use super::block::{Block, BlockType};
 
-/// Returns vertices matching the topology of `block` offset by `offset`.
-pub fn from_block(block: Block, offset: [f32, ..3], n: u32) -> (Vec<Vertex>, Vec<u32>) {
+/// The brightness of the lid for each lid remap.
+static LID_LIGHT: [f32, ..4] = [1.0, 0.85, 0.7, 0.55];
+/// The brightness of north and south faces. Like in the original
+/// game, side faces are darker than lids.
+static NORTH_SOUTH_SHADE: f32 = 0.8;
+/// The brightness of east and west faces.
+static EAST_WEST_SHADE: f32 = 0.65;
+
+/// Returns vertices matching the topology of `block` offset by `offset`,
+/// with the indices of opaque faces and of see-through faces. Only
+/// flat blocks, fences and railings, have see-through faces; colour 0
+/// of other blocks is drawn opaque.
+///
+/// With `tiles` the faces are textured with the block's tiles and
+/// faces without a tile are left out, otherwise every face is
+/// textured by block type.
+pub fn from_block(block: Block, offset: [f32, ..3], n: u32,
+                  tiles: Option<&TileAtlas>) -> (Vec<Vertex>, Vec<u32>, Vec<u32>) {
     let t = block.get_slope_type() as f32;
 
     // Calculate the lid vertices based on slope type.
@@ -32,69 +48,70 @@ pub fn from_block(block: Block, offset: [f32, ..3], n: u32) -> (Vec<Vertex>, Vec
         _ => (1.0, 1.0, 1.0, 1.0)
     }, block.get_lid_rotation());
 
-    let col = color_from_block_type(block.get_block_type());
+    let lid_col = shade(LID_LIGHT[block.get_remap_index() as uint]);
+    let north_south_col = shade(NORTH_SOUTH_SHADE);
+    let east_west_col = shade(EAST_WEST_SHADE);
     let (x, y, z) = (offset[0], offset[1], offset[2]);
 
     let mut vertices = Vec::new();
-    let mut indices = Vec::new();
-    
-    let tc = tex_coords(block.get_block_type());
-    // top
-    // if block.lid != 0 {
-        vertices.push_all(
-            vec!(Vertex::new([x +  0.0, y +  y3,  z +  1.0], tc[0], col),
-                 Vertex::new([x +  1.0, y +  y4,  z +  1.0], tc[1], col),
-                 Vertex::new([x +  0.0, y +  y1,  z +  0.0], tc[2], col),
-                 Vertex::new([x +  1.0, y +  y2,  z +  0.0], tc[3], col)
-            ).as_slice());
-    // }
-
-    // front
-    // if block.south != 0 {
-        vertices.push_all(
-            vec!(Vertex::new([x +  0.0, y +  0.0, z +  1.0], tc[0], col),
-                 Vertex::new([x +  1.0, y +  0.0, z +  1.0], tc[1], col),
-                 Vertex::new([x +  0.0, y +  y3,  z +  1.0], tc[2], col),
-                 Vertex::new([x +  1.0, y +  y4,  z +  1.0], tc[3], col),
-            ).as_slice());
-    // }
-
-    // back
-    // if block.north != 0 {
-        vertices.push_all(
-            vec!(Vertex::new([x +  1.0, y +  0.0, z +  0.0], tc[0], col),
-                 Vertex::new([x +  0.0, y +  0.0, z +  0.0], tc[1], col),
-                 Vertex::new([x +  1.0, y +  y2,  z +  0.0], tc[2], col),
-                 Vertex::new([x +  0.0, y +  y1,  z +  0.0], tc[3], col),
-                 ).as_slice());
-    // }
-
-    // right
-    // if block.east != 0 {
-        vertices.push_all(
-            vec!(Vertex::new([x +  1.0, y +  0.0, z +  1.0], tc[0], col),
-                 Vertex::new([x +  1.0, y +  0.0, z +  0.0], tc[1], col),
-                 Vertex::new([x +  1.0, y +  y4,  z +  1.0], tc[2], col),
-                 Vertex::new([x +  1.0, y +  y2,  z +  0.0], tc[3], col),
-                 ).as_slice());
-    // }
-
-    // left
-    // if block.west != 0 {
-        vertices.push_all(
-            vec!(Vertex::new([x +  0.0, y +  0.0, z +  0.0], tc[0], col),
-                 Vertex::new([x +  0.0, y +  0.0, z +  1.0], tc[1], col),
-                 Vertex::new([x +  0.0, y +  y1,  z +  0.0], tc[2], col),
-                 Vertex::new([x +  0.0, y +  y3,  z +  1.0], tc[3], col)
-                 ).as_slice());
-    // }
-
-    let num_indices = n + (vertices.len() as f32 * 1.5) as u32;
-    for i in range_step(n, num_indices, 4) {
-        indices.push_all(vec!(i + 0, i + 1, i + 2, i + 1, i + 2, i + 3).as_slice());
+    let mut opaque = Vec::new();
+    let mut transparent = Vec::new();
+
+    {
+        let block_tc = tex_coords(block.get_block_type());
+        // Adds a quad with `corners` in the order of the tile corners
+        // of a lid, textured with `face` of the block.
+        let mut push_face = |corners: [[f32, ..3], ..4], face: u8, is_lid: bool, color: [f32, ..3]| {
+            let (tc, see_through) = match tiles {
+                None => (block_tc, false),
+                Some(atlas) => {
+                    let tile = if is_lid { atlas.lid(face) } else { atlas.side(face) };
+                    let tile = match tile {
+                        Some(tile) => tile,
+                        None => return
+                    };
+                    let tc = atlas.tex_coords(tile);
+                    let tc = if is_lid {
+                        rotate_tex_coords(tc, block.get_lid_rotation())
+                    } else {
+                        // Side corners start at the bottom.
+                        [tc[2], tc[3], tc[0], tc[1]]
+                    };
+                    (tc, block.is_flat() && atlas.is_transparent(tile))
+                }
+            };
+
+            let i = n + vertices.len() as u32;
+            for (c, uv) in corners.iter().zip(tc.iter()) {
+                vertices.push(Vertex::new([x + c[0], y + c[1], z + c[2]], *uv, color));
+            }
+            let indices = if see_through { &mut transparent } else { &mut opaque };
+            indices.push_all([i + 0, i + 1, i + 2, i + 1, i + 2, i + 3]);
+        };
+
+        // top
+        push_face([[0.0, y3, 1.0], [1.0, y4, 1.0], [0.0, y1, 0.0], [1.0, y2, 0.0]],
+                  block.lid, true, lid_col);
+        // front
+        push_face([[0.0, 0.0, 1.0], [1.0, 0.0, 1.0], [0.0, y3, 1.0], [1.0, y4, 1.0]],
+                  block.south, false, north_south_col);
+        // back
+        push_face([[1.0, 0.0, 0.0], [0.0, 0.0, 0.0], [1.0, y2, 0.0], [0.0, y1, 0.0]],
+                  block.north, false, north_south_col);
+        // right
+        push_face([[1.0, 0.0, 1.0], [1.0, 0.0, 0.0], [1.0, y4, 1.0], [1.0, y2, 0.0]],
+                  block.east, false, east_west_col);
+        // left
+        push_face([[0.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, y1, 0.0], [0.0, y3, 1.0]],
+                  block.west, false, east_west_col);
     }
 
-    (vertices, indices)
+    (vertices, opaque, transparent)
+}
+
+/// Returns a grey vertex colour of `brightness`.
+fn shade(brightness: f32) -> [f32, ..3] {
+    [brightness, brightness, brightness]
 }
 
 /// Returns a color vector based on `BlockType`.
@@ -121,6 +138,16 @@ pub fn tex_coords(block_type: BlockType) -> [[f32, ..2], ..4] {
     }
 }
 
+/// Rotates the texture coordinates of a lid by `rot` degrees.
+fn rotate_tex_coords(tc: [[f32, ..2], ..4], rot: u16) -> [[f32, ..2], ..4] {
+    match rot {
+        90 => [tc[2], tc[0], tc[3], tc[1]],
+        180 => [tc[3], tc[2], tc[1], tc[0]],
+        270 => [tc[1], tc[3], tc[0], tc[2]],
+        _ => tc
+    }
+}
+
 /// Rotates the lid corners by `rot` degrees.
 fn rotate((y1, y2, y3, y4): (f32, f32, f32, f32), rot: u16) -> (f32, f32, f32, f32) {
     match rot {
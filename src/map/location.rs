@@ -1,10 +1,10 @@
-#[deriving(Show)]
+#[deriving(Show, Encodable, Decodable)]
 pub struct Location {
     pub location_type: LocationType,
     pub position: super::position::Position
 }
 
-#[deriving(Hash, Eq, PartialEq, Show)]
+#[deriving(Hash, Eq, PartialEq, Show, Encodable, Decodable)]
 pub enum LocationType {
     PoliceStation,
     Hospital,
@@ -2,6 +2,9 @@ use std::collections::HashMap;
 use std::vec::Vec;
 use std::io::{File, IoResult};
 use std::mem::size_of;
+use serialize::{json, Encodable};
+
+use json_file;
 
 pub mod block;
 pub mod block_data;
@@ -14,6 +17,7 @@ pub mod position;
 static header_size: u64 = 28;
 static base_size: u64 = 262144;
 
+#[deriving(Encodable, Decodable)]
 pub struct Map {
     pub blocks: Vec<Vec<Vec<block::Block>>>,
     pub objects: Vec<object::Object>,
@@ -59,6 +63,19 @@ impl Map {
             zones: zones
         })
     }
+
+    /// Loads a map saved with `save_json_file`.
+    pub fn from_json_file(filename: &str) -> Result<Map, String> {
+        println!("Loading map {}", filename);
+        json_file::load(&Path::new(filename))
+    }
+
+    /// Saves the map as JSON for editing by hand.
+    pub fn save_json_file(&self, filename: &str) -> IoResult<()> {
+        let mut f = try!(File::create(&Path::new(filename)));
+        let mut encoder = json::PrettyEncoder::new(&mut f);
+        self.encode(&mut encoder)
+    }
 }
 
 struct Header {
@@ -1,3 +1,8 @@
+/// Object positions, and the sizes in `ObjectInfo`, are in 64ths of a
+/// block.
+pub static UNITS_PER_BLOCK: f32 = 64.0;
+
+#[deriving(Encodable, Decodable)]
 pub struct Object {
     pub x: u16,
     pub y: u16,
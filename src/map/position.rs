@@ -1,4 +1,4 @@
-#[deriving(Show)]
+#[deriving(Show, Encodable, Decodable)]
 pub struct Position {
     pub x: u8,
     pub y: u8,
@@ -1,3 +1,4 @@
+#[deriving(Encodable, Decodable)]
 pub struct Route {
     pub route_type: u8,
     pub points: Vec<super::position::Position>
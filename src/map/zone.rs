@@ -1,9 +1,11 @@
+#[deriving(Encodable, Decodable)]
 pub struct Zone {
     pub area: Rect,
     pub sample: u8,
     pub name: String
 }
 
+#[deriving(Encodable, Decodable)]
 pub struct Rect {
     pub x: u8,
     pub y: u8,
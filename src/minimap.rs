@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use piston::image::{GenericImage, ImageBuf, Rgba};
+
+use map::{Map, block, block_data};
+use map::block::Block;
+use map::location;
+use map::object::UNITS_PER_BLOCK;
+use style::{Style, TILE_SIZE};
+
+/// The number of blocks along each side of a map.
+static MAP_SIZE: uint = 256;
+
+/// How each block of the overview is filled.
+pub enum Fill {
+    /// The colour of the top block's `BlockType`.
+    BlockTypeColor,
+    /// The top block's lid tile. Falls back to the block type colour
+    /// for blocks without a lid.
+    LidTile
+}
+
+pub struct MinimapOptions {
+    /// The size of a block in pixels.
+    pub scale: uint,
+    pub fill: Fill,
+    pub routes: bool,
+    pub locations: bool,
+    pub zones: bool,
+    pub objects: bool
+}
+
+impl MinimapOptions {
+    /// One pixel per block, coloured by block type, with every
+    /// overlay.
+    pub fn default() -> MinimapOptions {
+        MinimapOptions {
+            scale: 1,
+            fill: BlockTypeColor,
+            routes: true,
+            locations: true,
+            zones: true,
+            objects: true
+        }
+    }
+}
+
+/// Renders `map` as seen from above. `style` is needed for `LidTile`
+/// fills.
+pub fn render(map: &Map, style: Option<&Style>, opts: &MinimapOptions) -> ImageBuf<Rgba<u8>> {
+    let s = opts.scale;
+    let size = (MAP_SIZE * s) as u32;
+    let mut img = ImageBuf::from_pixel(size, size, Rgba(0, 0, 0, 255));
+    let mut lids: HashMap<uint, ImageBuf<Rgba<u8>>> = HashMap::new();
+
+    for x in range(0, MAP_SIZE) {
+        for y in range(0, MAP_SIZE) {
+            let top = match top_block(map, x, y) {
+                Some(b) => b,
+                None => continue
+            };
+
+            let color = to_rgba(block_data::color_from_block_type(top.get_block_type()));
+            let lid = match (opts.fill, style) {
+                (LidTile, Some(style)) if top.lid != 0 => {
+                    let tile = style.num_side_tiles + top.lid as uint;
+                    if !lids.contains_key(&tile) {
+                        match style.tile_image(tile, 0) {
+                            Ok(t) => { lids.insert(tile, t); },
+                            Err(_) => {}
+                        }
+                    }
+                    lids.find(&tile)
+                },
+                _ => None
+            };
+
+            for py in range(0, s) {
+                for px in range(0, s) {
+                    let c = match lid {
+                        Some(tile) => {
+                            let (tx, ty) = rotate(px * TILE_SIZE / s, py * TILE_SIZE / s,
+                                                  top.get_lid_rotation());
+                            tile.get_pixel(tx as u32, ty as u32)
+                        },
+                        None => color
+                    };
+                    img.put_pixel((x * s + px) as u32, (y * s + py) as u32, c);
+                }
+            }
+        }
+    }
+
+    if opts.zones {
+        for zone in map.zones.iter() {
+            let a = &zone.area;
+            draw_rect(&mut img, a.x as uint * s, a.y as uint * s,
+                      a.width as uint * s, a.height as uint * s, Rgba(255, 255, 0, 255));
+        }
+    }
+
+    if opts.routes {
+        for route in map.routes.iter() {
+            for pair in route.points.as_slice().windows(2) {
+                draw_line(&mut img,
+                          center(pair[0].x as uint, s), center(pair[0].y as uint, s),
+                          center(pair[1].x as uint, s), center(pair[1].y as uint, s),
+                          Rgba(0, 255, 255, 255));
+            }
+        }
+    }
+
+    if opts.objects {
+        for obj in map.objects.iter() {
+            let x = (obj.x as f32 * s as f32 / UNITS_PER_BLOCK) as uint;
+            let y = (obj.y as f32 * s as f32 / UNITS_PER_BLOCK) as uint;
+            put(&mut img, x, y, Rgba(255, 0, 255, 255));
+        }
+    }
+
+    if opts.locations {
+        for (location_type, locations) in map.locations.iter() {
+            let color = match *location_type {
+                location::PoliceStation => Rgba(0, 0, 255, 255),
+                location::Hospital => Rgba(255, 255, 255, 255),
+                location::FireStation => Rgba(255, 0, 0, 255),
+                location::Unknown => Rgba(128, 128, 128, 255)
+            };
+            for l in locations.iter() {
+                let (x, y) = (center(l.position.x as uint, s), center(l.position.y as uint, s));
+                let r = if s > 2 { s / 2 } else { 1 };
+                for dy in range(0, 2 * r + 1) {
+                    for dx in range(0, 2 * r + 1) {
+                        if x + dx >= r && y + dy >= r {
+                            put(&mut img, x + dx - r, y + dy - r, color);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    img
+}
+
+/// Returns the highest non-air block of column `x`, `y`.
+fn top_block(map: &Map, x: uint, y: uint) -> Option<Block> {
+    map.blocks[x][y].iter().rev()
+        .find(|b| b.get_block_type() != block::Air)
+        .map(|b| *b)
+}
+
+/// Rotates tile coordinates by `rot` degrees.
+fn rotate(x: uint, y: uint, rot: u16) -> (uint, uint) {
+    let max = TILE_SIZE - 1;
+    match rot {
+        90 => (y, max - x),
+        180 => (max - x, max - y),
+        270 => (max - y, x),
+        _ => (x, y)
+    }
+}
+
+fn center(block: uint, scale: uint) -> uint {
+    block * scale + scale / 2
+}
+
+fn to_rgba(c: [f32, ..3]) -> Rgba<u8> {
+    Rgba((c[0] * 255.0) as u8, (c[1] * 255.0) as u8, (c[2] * 255.0) as u8, 255)
+}
+
+fn put(img: &mut ImageBuf<Rgba<u8>>, x: uint, y: uint, c: Rgba<u8>) {
+    let (w, h) = img.dimensions();
+    if (x as u32) < w && (y as u32) < h {
+        img.put_pixel(x as u32, y as u32, c);
+    }
+}
+
+fn draw_rect(img: &mut ImageBuf<Rgba<u8>>, x: uint, y: uint, w: uint, h: uint, c: Rgba<u8>) {
+    if w == 0 || h == 0 {
+        return;
+    }
+    for i in range(0, w) {
+        put(img, x + i, y, c);
+        put(img, x + i, y + h - 1, c);
+    }
+    for i in range(0, h) {
+        put(img, x, y + i, c);
+        put(img, x + w - 1, y + i, c);
+    }
+}
+
+/// Draws a line with Bresenham's algorithm.
+fn draw_line(img: &mut ImageBuf<Rgba<u8>>, x0: uint, y0: uint, x1: uint, y1: uint, c: Rgba<u8>) {
+    let (mut x, mut y) = (x0 as int, y0 as int);
+    let (x1, y1) = (x1 as int, y1 as int);
+    let dx = if x1 > x { x1 - x } else { x - x1 };
+    let dy = if y1 > y { y - y1 } else { y1 - y };
+    let sx = if x < x1 { 1 } else { -1 };
+    let sy = if y < y1 { 1 } else { -1 };
+    let mut err = dx + dy;
+    loop {
+        put(img, x as uint, y as uint, c);
+        if x == x1 && y == y1 {
+            break;
+        }
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y += sy;
+        }
+    }
+}
@@ -1,3 +1,6 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
 use gfx;
 use gfx::{Device, DeviceHelper, VertexFormat, BufferHandle, ToSlice};
 use gfx::shade::ShaderParam;
@@ -5,32 +8,118 @@ use device;
 use device::draw::CommandBuffer;
 use render::batch::RefBatch;
 
-use super::Renderer;
-use super::program::Program;
+use super::{Renderer, Pass};
+use super::program::ProgramError;
+
+/// The GPU buffers of dropped `Buffer`s, freed by the renderer that
+/// made them.
+pub type Garbage = Rc<RefCell<Vec<device::RawBufferHandle>>>;
 
+/// Vertex and index data on the GPU, ready to be drawn with a
+/// program. Register it with `Renderer::add` to have it drawn. The
+/// GPU buffers are freed by the renderer after the buffer is dropped,
+/// whether it was registered or not.
 pub struct Buffer<V: VertexFormat + Copy, P: ShaderParam<L>, L> {
     pub buf: BufferHandle<V>,
-    pub batch: RefBatch<L, P>
+    pub index_buf: BufferHandle<u32>,
+    pub batch: RefBatch<L, P>,
+    /// The name of the program the batch was made with.
+    pub program: String,
+    mesh: gfx::Mesh,
+    slice: gfx::Slice,
+    drawstate: gfx::DrawState,
+    garbage: Garbage
 }
 
 impl<V: VertexFormat + Copy, P: ShaderParam<L>, L> Buffer<V, P, L> {
-    /// Creates a new buffer from `vertex_data` and `index_data`.
+    /// Creates a new buffer from `vertex_data` and `index_data`, to
+    /// be drawn in `pass` with the program `program`.
     pub fn new<D: Device<C>, C: CommandBuffer>(r: &mut Renderer<D, C>,
-                                               program: &Program,
+                                               program: &str,
                                                vertex_data: &[V],
-                                               index_data: &[u32]) -> Buffer<V, P, L> {
-        let buf = r.graphics.device.create_buffer(vertex_data.len(), gfx::UsageStatic);
+                                               index_data: &[u32],
+                                               pass: Pass) -> Result<Buffer<V, P, L>, ProgramError> {
+        Buffer::with_usage(r, program, vertex_data, index_data, pass, gfx::UsageStatic)
+    }
+
+    /// Like `new`, but the vertices are expected to be replaced with
+    /// `update` often, e.g. every frame.
+    pub fn dynamic<D: Device<C>, C: CommandBuffer>(r: &mut Renderer<D, C>,
+                                                   program: &str,
+                                                   vertex_data: &[V],
+                                                   index_data: &[u32],
+                                                   pass: Pass) -> Result<Buffer<V, P, L>, ProgramError> {
+        Buffer::with_usage(r, program, vertex_data, index_data, pass, gfx::UsageDynamic)
+    }
+
+    fn with_usage<D: Device<C>, C: CommandBuffer>(r: &mut Renderer<D, C>,
+                                                  program: &str,
+                                                  vertex_data: &[V],
+                                                  index_data: &[u32],
+                                                  pass: Pass,
+                                                  usage: gfx::BufferUsage)
+                                                  -> Result<Buffer<V, P, L>, ProgramError> {
+        let handle = try!(r.load_program(program)).handle.clone();
+        let buf = r.graphics.device.create_buffer(vertex_data.len(), usage);
         r.graphics.device.update_buffer(buf, vertex_data, 0);
 
         let mesh = gfx::Mesh::from_format(buf, vertex_data.len() as u32);
-        let slice = r.graphics.device.create_buffer_static::<u32>(index_data)
-            .to_slice(gfx::TriangleList);
+        let index_buf = r.graphics.device.create_buffer_static::<u32>(index_data);
+        let slice = index_buf.to_slice(gfx::TriangleList);
 
-        Buffer {
+        let drawstate = r.drawstate(pass);
+        let batch = try!(make_batch(&mut r.graphics, program, &handle,
+                                    &mesh, slice, &drawstate));
+        Ok(Buffer {
             buf: buf,
-            batch: r.graphics.make_batch(&program.handle, &mesh,
-                                         slice,
-                                         &r.drawstate).unwrap()
-        }
+            index_buf: index_buf,
+            batch: batch,
+            program: program.to_string(),
+            mesh: mesh,
+            slice: slice,
+            drawstate: drawstate,
+            garbage: r.garbage()
+        })
+    }
+
+    /// Replaces the vertices, which must be no more than the buffer
+    /// was created with.
+    pub fn update<D: Device<C>, C: CommandBuffer>(&self, device: &mut D, vertex_data: &[V]) {
+        device.update_buffer(self.buf, vertex_data, 0);
     }
+
+    /// Makes a batch of the buffer with `handle`, a relinked version of
+    /// its program, to replace `batch` with.
+    pub fn rebatch<D: Device<C>, C: CommandBuffer>(&self,
+                                                   graphics: &mut gfx::Graphics<D, C>,
+                                                   handle: &device::Handle<u32, device::shade::ProgramInfo>)
+                                                   -> Result<RefBatch<L, P>, ProgramError> {
+        make_batch(graphics, self.program.as_slice(), handle,
+                   &self.mesh, self.slice, &self.drawstate)
+    }
+
+}
+
+#[unsafe_destructor]
+impl<V: VertexFormat + Copy, P: ShaderParam<L>, L> Drop for Buffer<V, P, L> {
+    /// Hands the vertex and index buffers to the renderer to free.
+    fn drop(&mut self) {
+        let mut garbage = self.garbage.borrow_mut();
+        garbage.push(self.buf.raw());
+        garbage.push(self.index_buf.raw());
+    }
+}
+
+fn make_batch<D: Device<C>, C: CommandBuffer, P: ShaderParam<L>, L>(
+        graphics: &mut gfx::Graphics<D, C>,
+        name: &str,
+        program: &device::Handle<u32, device::shade::ProgramInfo>,
+        mesh: &gfx::Mesh,
+        slice: gfx::Slice,
+        drawstate: &gfx::DrawState) -> Result<RefBatch<L, P>, ProgramError> {
+    graphics.make_batch(program, mesh, slice, drawstate).map_err(|e| ProgramError {
+        name: name.to_string(),
+        desc: "parameters do not match the program".to_string(),
+        log: Some(format!("{}", e))
+    })
 }
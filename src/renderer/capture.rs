@@ -0,0 +1,54 @@
+use std::io::{File, IoResult, IoError, OtherIoError};
+
+use gl;
+use gl::types::{GLint, GLsizei, GLvoid};
+use piston::image;
+use piston::image::{ImageBuf, Rgba};
+
+/// Reads a `width` by `height` image from the lower left corner of
+/// the framebuffer being drawn to. Call after the frame's commands
+/// are flushed and before the buffers are swapped.
+pub fn read_framebuffer(width: u32, height: u32) -> ImageBuf<Rgba<u8>> {
+    let mut pixels = Vec::from_elem((width * height * 4) as uint, 0u8);
+    unsafe {
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+        gl::ReadPixels(0, 0, width as GLsizei, height as GLsizei,
+                       gl::RGBA, gl::UNSIGNED_BYTE,
+                       pixels.as_mut_ptr() as *mut GLvoid);
+    }
+    from_gl(pixels, width, height)
+}
+
+/// Reads level 0 of the RGBA8 texture `name`.
+pub fn read_texture(name: u32, width: u32, height: u32) -> ImageBuf<Rgba<u8>> {
+    let mut pixels = Vec::from_elem((width * height * 4) as uint, 0u8);
+    unsafe {
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+        gl::BindTexture(gl::TEXTURE_2D, name);
+        gl::GetTexImage(gl::TEXTURE_2D, 0 as GLint, gl::RGBA, gl::UNSIGNED_BYTE,
+                        pixels.as_mut_ptr() as *mut GLvoid);
+        gl::BindTexture(gl::TEXTURE_2D, 0);
+    }
+    from_gl(pixels, width, height)
+}
+
+/// Saves `img` as a PNG file.
+pub fn save_png(img: ImageBuf<Rgba<u8>>, path: &Path) -> IoResult<()> {
+    let fout = try!(File::create(path));
+    match image::ImageRgba8(img).save(fout, image::PNG) {
+        Ok(()) => Ok(()),
+        Err(why) => Err(IoError {
+            kind: OtherIoError,
+            desc: "Could not encode PNG",
+            detail: Some(format!("{}: {}", path.display(), why))
+        })
+    }
+}
+
+/// Turns RGBA rows read from GL, bottom row first, into an image.
+fn from_gl(pixels: Vec<u8>, width: u32, height: u32) -> ImageBuf<Rgba<u8>> {
+    ImageBuf::from_fn(width, height, |x, y| {
+        let i = (((height - 1 - y) * width + x) * 4) as uint;
+        Rgba(pixels[i], pixels[i + 1], pixels[i + 2], pixels[i + 3])
+    })
+}
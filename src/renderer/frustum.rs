@@ -0,0 +1,111 @@
+use super::mat4_mul;
+
+/// An axis-aligned bounding box in world coordinates.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Aabb {
+    pub min: [f32, ..3],
+    pub max: [f32, ..3]
+}
+
+impl Aabb {
+    /// Returns the smallest box containing `points`, or `None` if
+    /// there are none.
+    pub fn from_points<'a, I: Iterator<&'a [f32, ..3]>>(mut points: I) -> Option<Aabb> {
+        let first = match points.next() {
+            Some(p) => *p,
+            None => return None
+        };
+        let mut aabb = Aabb { min: first, max: first };
+        for p in points {
+            for i in range(0, 3) {
+                if p[i] < aabb.min[i] { aabb.min[i] = p[i]; }
+                if p[i] > aabb.max[i] { aabb.max[i] = p[i]; }
+            }
+        }
+        Some(aabb)
+    }
+}
+
+/// The six planes bounding what a camera sees. Each plane is
+/// `[a, b, c, d]` with the normal pointing inwards, so a point is
+/// inside when `a * x + b * y + c * z + d >= 0` for every plane.
+pub struct Frustum {
+    planes: [[f32, ..4], ..6]
+}
+
+impl Frustum {
+    /// Extracts the frustum of column-major `projection` and `view`
+    /// matrices, as passed to the shaders.
+    pub fn from_matrices(projection: [[f32, ..4], ..4], view: [[f32, ..4], ..4]) -> Frustum {
+        let m = mat4_mul(projection, view);
+        // Row `r` of the combined matrix.
+        let row = |r: uint| [m[0][r], m[1][r], m[2][r], m[3][r]];
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        let add = |a: [f32, ..4], b: [f32, ..4]| [a[0] + b[0], a[1] + b[1], a[2] + b[2], a[3] + b[3]];
+        let sub = |a: [f32, ..4], b: [f32, ..4]| [a[0] - b[0], a[1] - b[1], a[2] - b[2], a[3] - b[3]];
+        Frustum {
+            planes: [add(w, x), sub(w, x), add(w, y), sub(w, y), add(w, z), sub(w, z)]
+        }
+    }
+
+    /// Whether any part of `aabb` may be visible. Boxes near the
+    /// corners of the frustum can be reported visible when they are
+    /// not, but never the other way around.
+    pub fn intersects(&self, aabb: &Aabb) -> bool {
+        for p in self.planes.iter() {
+            // The corner furthest along the plane normal.
+            let corner = [
+                if p[0] >= 0.0 { aabb.max[0] } else { aabb.min[0] },
+                if p[1] >= 0.0 { aabb.max[1] } else { aabb.min[1] },
+                if p[2] >= 0.0 { aabb.max[2] } else { aabb.min[2] }
+            ];
+            if p[0] * corner[0] + p[1] * corner[1] + p[2] * corner[2] + p[3] < 0.0 {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Aabb, Frustum};
+
+    static IDENTITY: [[f32, ..4], ..4] = [[1.0, 0.0, 0.0, 0.0],
+                                          [0.0, 1.0, 0.0, 0.0],
+                                          [0.0, 0.0, 1.0, 0.0],
+                                          [0.0, 0.0, 0.0, 1.0]];
+
+    fn aabb(min: [f32, ..3], max: [f32, ..3]) -> Aabb {
+        Aabb { min: min, max: max }
+    }
+
+    #[test]
+    fn keeps_a_box_inside() {
+        let frustum = Frustum::from_matrices(IDENTITY, IDENTITY);
+        assert!(frustum.intersects(&aabb([-0.5, -0.5, -0.5], [0.5, 0.5, 0.5])));
+    }
+
+    #[test]
+    fn culls_a_box_outside() {
+        let frustum = Frustum::from_matrices(IDENTITY, IDENTITY);
+        assert!(!frustum.intersects(&aabb([2.0, -0.5, -0.5], [3.0, 0.5, 0.5])));
+        assert!(!frustum.intersects(&aabb([-0.5, -0.5, -3.0], [0.5, 0.5, -2.0])));
+    }
+
+    #[test]
+    fn keeps_a_box_across_a_plane() {
+        let frustum = Frustum::from_matrices(IDENTITY, IDENTITY);
+        assert!(frustum.intersects(&aabb([0.5, -0.5, -0.5], [1.5, 0.5, 0.5])));
+    }
+
+    #[test]
+    fn applies_the_view() {
+        // Moves the world 2 along x, so the clip volume covers x in [-3, -1].
+        let mut view = IDENTITY;
+        view[3][0] = 2.0;
+        let frustum = Frustum::from_matrices(IDENTITY, view);
+        assert!(frustum.intersects(&aabb([-2.5, -0.5, -0.5], [-1.5, 0.5, 0.5])));
+        assert!(!frustum.intersects(&aabb([-0.5, -0.5, -0.5], [0.5, 0.5, 0.5])));
+    }
+}
@@ -1,16 +1,30 @@
 #[phase(plugin)]
 extern crate gfx_macros;
 
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::mem;
+use std::rc::Rc;
+
 use gfx;
-use gfx::{Device, VertexFormat};
-use gfx::shade::{ShaderParam, TextureParam};
+use gfx::Device;
+use gfx::shade::TextureParam;
 use device;
 use device::draw::CommandBuffer;
+use assets::Assets;
+use lighting::LightUniforms;
 use piston::image;
 use piston::image::{GenericImage, ImageBuf, MutableRefImage, Pixel, Rgba};
 
 pub mod buffer;
+pub mod capture;
+pub mod frustum;
 pub mod program;
+pub mod software;
+pub mod sprite_batch;
+pub mod tile_atlas;
+
+pub use self::program::ProgramError;
 
 #[vertex_format]
 pub struct Vertex {
@@ -29,7 +43,20 @@ pub struct Params {
     #[name = "view"]
     pub view: [[f32, ..4], ..4],
     #[name = "s_texture"]
-    pub texture: TextureParam
+    pub texture: TextureParam,
+    #[name = "ambient"]
+    pub ambient: [f32, ..3],
+    #[name = "fog_color"]
+    pub fog_color: [f32, ..3],
+    #[name = "fog_density"]
+    pub fog_density: f32,
+    #[name = "light_pos"]
+    pub light_pos: [[f32, ..4], ..4],
+    #[name = "light_color"]
+    pub light_color: [[f32, ..4], ..4],
+    /// 1 to discard see-through texels, 0 to draw them opaque.
+    #[name = "alpha_test"]
+    pub alpha_test: f32
 }
 
 impl Vertex {
@@ -82,7 +109,8 @@ impl Texture {
     }
 }
 
-fn load_rgba8(path: &Path) -> Result<ImageBuf<Rgba<u8>>, String> {
+/// Loads an RGB or RGBA image as RGBA.
+pub fn load_rgba8(path: &Path) -> Result<ImageBuf<Rgba<u8>>, String> {
     match image::open(path) {
         Ok(image::ImageRgba8(img)) => Ok(img),
         Ok(image::ImageRgb8(img)) => {
@@ -95,18 +123,95 @@ fn load_rgba8(path: &Path) -> Result<ImageBuf<Rgba<u8>>, String> {
     }
 }
 
+/// Multiplies two column-major matrices.
+pub fn mat4_mul(a: [[f32, ..4], ..4], b: [[f32, ..4], ..4]) -> [[f32, ..4], ..4] {
+    let mut m = [[0.0, ..4], ..4];
+    for col in range(0, 4u) {
+        m[col] = mat4_transform(a, b[col]);
+    }
+    m
+}
+
+/// Transforms `v` by column-major matrix `m`.
+pub fn mat4_transform(m: [[f32, ..4], ..4], v: [f32, ..4]) -> [f32, ..4] {
+    let mut out = [0.0, ..4];
+    for row in range(0, 4u) {
+        out[row] = m[0][row] * v[0] + m[1][row] * v[1] + m[2][row] * v[2] + m[3][row] * v[3];
+    }
+    out
+}
+
+/// A draw pass. Passes are drawn in this order.
+#[deriving(PartialEq, PartialOrd, Show)]
+pub enum Pass {
+    /// Depth tested and written.
+    Opaque,
+    /// Alpha blended, depth tested but not written.
+    Transparent,
+    /// Alpha blended on top of everything, e.g. the HUD.
+    Overlay
+}
+
+/// Refers to a buffer registered with `Renderer::add`.
+#[deriving(PartialEq, Show)]
+pub struct BufferId {
+    index: uint,
+    generation: uint
+}
+
+/// A registered buffer and how to draw it.
+struct Entry {
+    buffer: buffer::Buffer<Vertex, Params, _ParamsLink>,
+    pass: Pass,
+    /// Entries of the same pass are drawn in ascending order.
+    order: int,
+    texture: TextureParam,
+    /// Where the vertices are, for culling. Entries without bounds
+    /// are always drawn.
+    bounds: Option<frustum::Aabb>,
+    /// The chunk the vertices belong to, for `Stats`.
+    chunk: Option<uint>,
+    visible: bool,
+    /// Whether see-through texels are discarded rather than drawn
+    /// opaque.
+    alpha_test: bool
+}
+
+/// What the last `Renderer::draw` did.
+#[deriving(Clone, Show)]
+pub struct Stats {
+    /// Map chunks drawn.
+    pub drawn: uint,
+    /// Map chunks outside the view, skipped.
+    pub culled: uint
+}
+
+struct Slot {
+    generation: uint,
+    entry: Option<Entry>
+}
+
 pub struct Renderer<D: Device<C>, C: CommandBuffer> {
     pub graphics: gfx::Graphics<D, C>,
     frame: gfx::Frame,
     clear_data: gfx::ClearData,
-    drawstate: gfx::DrawState
+    slots: Vec<Slot>,
+    /// Resolves program sources, so mods can replace shaders.
+    assets: Assets,
+    programs: HashMap<String, program::Program>,
+    projection: [[f32, ..4], ..4],
+    view: [[f32, ..4], ..4],
+    lights: LightUniforms,
+    stats: Stats,
+    /// The colour and depth textures drawn to instead of the window,
+    /// if any.
+    target: Option<(gfx::TextureHandle, gfx::TextureHandle)>,
+    /// The GPU buffers of dropped buffers, to be freed.
+    garbage: buffer::Garbage
 }
 
 impl<D: Device<C>, C: CommandBuffer> Renderer<D, C> {
-    pub fn new(mut device: D, frame: gfx::Frame) -> Renderer<D, C> {
-        let mut drawstate = gfx::DrawState::new().depth(gfx::state::LessEqual, true);
-        drawstate.primitive.front_face = gfx::state::CounterClockwise;
-
+    pub fn new(mut device: D, frame: gfx::Frame, assets: Assets) -> Renderer<D, C> {
         Renderer {
             graphics: gfx::Graphics::new(device),
             frame: frame,
@@ -115,21 +220,329 @@ impl<D: Device<C>, C: CommandBuffer> Renderer<D, C> {
                 depth: 1.0,
                 stencil: 0,
             },
-            drawstate: drawstate,
+            slots: Vec::new(),
+            assets: assets,
+            programs: HashMap::new(),
+            projection: [[0.0, ..4], ..4],
+            view: [[0.0, ..4], ..4],
+            lights: LightUniforms::flat(),
+            stats: Stats { drawn: 0, culled: 0 },
+            target: None,
+            garbage: Rc::new(RefCell::new(Vec::new()))
+        }
+    }
+
+    /// Returns the program `name`, loading it the first time it is
+    /// asked for.
+    pub fn load_program(&mut self, name: &str) -> Result<&program::Program, ProgramError> {
+        if !self.programs.contains_key_equiv(&name) {
+            let program = try!(program::Program::load(&mut self.graphics.device,
+                                                      &self.assets, name));
+            self.programs.insert(name.to_string(), program);
+        }
+        Ok(self.programs.find_equiv(&name).unwrap())
+    }
+
+    /// Relinks the programs whose sources changed on disk and remakes
+    /// the batches using them. A program is only replaced once every
+    /// batch was remade; returns the errors of programs that were not,
+    /// which keep their previous version.
+    pub fn reload_programs(&mut self) -> Vec<ProgramError> {
+        let mut errors = Vec::new();
+        for (name, program) in self.programs.iter_mut() {
+            if !program.is_stale() {
+                continue;
+            }
+            let handle = match program.relink(&mut self.graphics.device, &self.assets) {
+                Ok(handle) => handle,
+                Err(e) => {
+                    errors.push(e);
+                    continue;
+                }
+            };
+
+            let mut batches = Vec::new();
+            let mut failed = None;
+            for (i, slot) in self.slots.iter().enumerate() {
+                match slot.entry {
+                    Some(ref e) if e.buffer.program == *name => {
+                        match e.buffer.rebatch(&mut self.graphics, &handle) {
+                            Ok(batch) => batches.push((i, batch)),
+                            Err(why) => {
+                                failed = Some(why);
+                                break;
+                            }
+                        }
+                    },
+                    _ => {}
+                }
+            }
+
+            match failed {
+                Some(e) => {
+                    self.graphics.device.delete_program(handle);
+                    errors.push(e);
+                },
+                None => {
+                    for (i, batch) in batches.into_iter() {
+                        self.slots.get_mut(i).entry.as_mut().unwrap().buffer.batch = batch;
+                    }
+                    let old = program.replace(handle);
+                    self.graphics.device.delete_program(old);
+                }
+            }
+        }
+        errors
+    }
+
+    /// Returns the draw state of `pass`.
+    pub fn drawstate(&self, pass: Pass) -> gfx::DrawState {
+        let mut drawstate = match pass {
+            Opaque => gfx::DrawState::new().depth(gfx::state::LessEqual, true),
+            Transparent => gfx::DrawState::new()
+                .depth(gfx::state::LessEqual, false)
+                .blend(gfx::BlendAlpha),
+            Overlay => gfx::DrawState::new().blend(gfx::BlendAlpha)
+        };
+        drawstate.primitive.front_face = gfx::state::CounterClockwise;
+        drawstate
+    }
+
+    /// Registers `buffer` to be drawn in `pass` with `texture` every
+    /// frame, until it is removed.
+    pub fn add(&mut self, buffer: buffer::Buffer<Vertex, Params, _ParamsLink>,
+               pass: Pass, texture: TextureParam) -> BufferId {
+        let entry = Entry {
+            buffer: buffer,
+            pass: pass,
+            order: 0,
+            texture: texture,
+            bounds: None,
+            chunk: None,
+            visible: true,
+            alpha_test: true
+        };
+
+        match self.slots.iter().position(|s| s.entry.is_none()) {
+            Some(index) => {
+                let slot = self.slots.get_mut(index);
+                slot.entry = Some(entry);
+                BufferId { index: index, generation: slot.generation }
+            },
+            None => {
+                self.slots.push(Slot { generation: 0, entry: Some(entry) });
+                BufferId { index: self.slots.len() - 1, generation: 0 }
+            }
+        }
+    }
+
+    /// Unregisters the buffer `id` and frees its GPU resources.
+    pub fn remove(&mut self, id: BufferId) {
+        if !self.is_valid(id) {
+            return;
+        }
+        {
+            let slot = self.slots.get_mut(id.index);
+            slot.generation += 1;
+            slot.entry = None;
+        }
+        self.free_garbage();
+    }
+
+    /// Returns the list dropped buffers put their GPU buffers on.
+    pub fn garbage(&self) -> buffer::Garbage {
+        self.garbage.clone()
+    }
+
+    /// Frees the GPU buffers of the buffers dropped so far.
+    fn free_garbage(&mut self) {
+        let handles = mem::replace(&mut *self.garbage.borrow_mut(), Vec::new());
+        for handle in handles.into_iter() {
+            self.graphics.device.delete_buffer_raw(handle);
+        }
+    }
+
+    /// Replaces the vertices of buffer `id`, see `Buffer::update`.
+    pub fn update(&mut self, id: BufferId, vertex_data: &[Vertex]) {
+        if self.is_valid(id) {
+            let entry = self.slots[id.index].entry.as_ref().unwrap();
+            entry.buffer.update(&mut self.graphics.device, vertex_data);
         }
     }
 
+    /// Sets the order of buffer `id` within its pass.
+    pub fn set_order(&mut self, id: BufferId, order: int) {
+        if self.is_valid(id) {
+            self.slots.get_mut(id.index).entry.as_mut().unwrap().order = order;
+        }
+    }
+
+    /// Sets the bounding box of buffer `id`, so it is skipped when
+    /// outside the camera's view.
+    pub fn set_bounds(&mut self, id: BufferId, bounds: frustum::Aabb) {
+        if self.is_valid(id) {
+            self.slots.get_mut(id.index).entry.as_mut().unwrap().bounds = Some(bounds);
+        }
+    }
+
+    /// Marks buffer `id` as part of map chunk `chunk`, to be counted
+    /// in `Stats`. A chunk may be drawn with several buffers.
+    pub fn set_chunk(&mut self, id: BufferId, chunk: uint) {
+        if self.is_valid(id) {
+            self.slots.get_mut(id.index).entry.as_mut().unwrap().chunk = Some(chunk);
+        }
+    }
+
+    /// Sets whether the see-through texels of buffer `id`, colour
+    /// index 0, are discarded or drawn opaque. They are discarded
+    /// unless set otherwise.
+    pub fn set_alpha_test(&mut self, id: BufferId, alpha_test: bool) {
+        if self.is_valid(id) {
+            self.slots.get_mut(id.index).entry.as_mut().unwrap().alpha_test = alpha_test;
+        }
+    }
+
+    /// Shows or hides buffer `id` without freeing it.
+    pub fn set_visible(&mut self, id: BufferId, visible: bool) {
+        if self.is_valid(id) {
+            self.slots.get_mut(id.index).entry.as_mut().unwrap().visible = visible;
+        }
+    }
+
+    /// Returns what the last `draw` did.
+    pub fn stats(&self) -> Stats {
+        self.stats.clone()
+    }
+
+    /// Resizes the frame drawn to, after the window was resized.
+    /// Ignored when drawing offscreen.
+    pub fn resize(&mut self, width: u16, height: u16) {
+        if self.target.is_none() {
+            self.frame = gfx::Frame::new(width, height);
+        }
+    }
+
+    /// Draws to a `width` by `height` texture from now on instead of
+    /// the window, so frames can be captured whatever the window's
+    /// size or visibility.
+    pub fn draw_offscreen(&mut self, width: u16, height: u16) {
+        self.free_target();
+        let mut ti = gfx::tex::TextureInfo::new();
+        ti.width = width;
+        ti.height = height;
+        ti.kind = gfx::tex::Texture2D;
+        ti.format = gfx::tex::RGBA8;
+        let color = self.graphics.device.create_texture(ti).unwrap();
+        ti.format = gfx::tex::DEPTH24STENCIL8;
+        let depth = self.graphics.device.create_texture(ti).unwrap();
+
+        let mut frame = gfx::Frame::new(width, height);
+        frame.colors.push(gfx::PlaneTexture(color.clone(), 0, None));
+        frame.depth = Some(gfx::PlaneTexture(depth.clone(), 0, None));
+        self.frame = frame;
+        self.target = Some((color, depth));
+    }
+
+    /// Frees the textures made by `draw_offscreen`, if any.
+    fn free_target(&mut self) {
+        match self.target.take() {
+            Some((color, depth)) => {
+                self.graphics.device.delete_texture(color);
+                self.graphics.device.delete_texture(depth);
+            },
+            None => {}
+        }
+    }
+
+    /// Reads back the last frame. Call after `end_frame`.
+    pub fn screenshot(&self) -> ImageBuf<Rgba<u8>> {
+        let (width, height) = (self.frame.width as u32, self.frame.height as u32);
+        match self.target {
+            Some((ref color, _)) => capture::read_texture(color.get_name(), width, height),
+            None => capture::read_framebuffer(width, height)
+        }
+    }
+
+    /// Sets the lighting used by the next `draw`.
+    pub fn set_lighting(&mut self, lights: LightUniforms) {
+        self.lights = lights;
+    }
+
+    /// Sets the camera used by the next `draw`.
+    pub fn set_camera(&mut self, projection: [[f32, ..4], ..4], view: [[f32, ..4], ..4]) {
+        self.projection = projection;
+        self.view = view;
+    }
+
     pub fn clear(&mut self) {
         self.graphics.clear(self.clear_data, gfx::COLOR | gfx::DEPTH, &self.frame);
     }
 
-    pub fn render<V: VertexFormat, P: ShaderParam<L>, L>(&mut self,
-                                                         buffer: buffer::Buffer<V, P, L>,
-                                                         params: P) {
-        self.graphics.draw(&buffer.batch, &params, &self.frame);
+    /// Draws every registered buffer in view, pass by pass.
+    pub fn draw(&mut self) {
+        let frustum = frustum::Frustum::from_matrices(self.projection, self.view);
+        let mut drawn = HashSet::new();
+        let mut culled = HashSet::new();
+
+        let mut order: Vec<(Pass, int, uint)> = self.slots.iter().enumerate()
+            .filter_map(|(i, s)| match s.entry {
+                Some(ref e) if e.visible => Some((e.pass, e.order, i)),
+                _ => None
+            })
+            .collect();
+        order.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        for &(_, _, i) in order.iter() {
+            let entry = self.slots[i].entry.as_ref().unwrap();
+            let inside = entry.bounds.as_ref().map_or(true, |b| frustum.intersects(b));
+            match entry.chunk {
+                Some(chunk) if inside => { drawn.insert(chunk); },
+                Some(chunk) => { culled.insert(chunk); },
+                None => {}
+            }
+            if !inside {
+                continue;
+            }
+            let params = Params {
+                projection: self.projection,
+                view: self.view,
+                texture: entry.texture,
+                ambient: self.lights.ambient,
+                fog_color: self.lights.fog_color,
+                fog_density: self.lights.fog_density,
+                light_pos: self.lights.light_pos,
+                light_color: self.lights.light_color,
+                alpha_test: if entry.alpha_test { 1.0 } else { 0.0 }
+            };
+            self.graphics.draw(&entry.buffer.batch, &params, &self.frame);
+        }
+        self.stats = Stats { drawn: drawn.len(), culled: culled.len() };
     }
 
     pub fn end_frame(&mut self) {
         self.graphics.end_frame();
+        self.free_garbage();
+    }
+
+    fn is_valid(&self, id: BufferId) -> bool {
+        id.index < self.slots.len() &&
+            self.slots[id.index].generation == id.generation &&
+            self.slots[id.index].entry.is_some()
+    }
+}
+
+#[unsafe_destructor]
+impl<D: Device<C>, C: CommandBuffer> Drop for Renderer<D, C> {
+    /// Frees the buffers still registered, the programs and the
+    /// offscreen target.
+    fn drop(&mut self) {
+        self.slots.clear();
+        self.free_garbage();
+
+        let programs = mem::replace(&mut self.programs, HashMap::new());
+        for (_, program) in programs.into_iter() {
+            self.graphics.device.delete_program(program.handle);
+        }
+        self.free_target();
     }
 }
@@ -1,49 +1,194 @@
-use std::io::{File, IoResult};
+use std::fmt;
+use std::mem;
+use std::io::File;
+use std::io::fs::PathExtensions;
 
 use gfx;
 use gfx::{Device, DeviceHelper};
 use device;
 use device::draw::CommandBuffer;
 
-use super::{Renderer};
+use assets::Assets;
 
+/// The GLSL versions a program can provide sources for, in the order
+/// of the fields of `gfx::ShaderSource`.
+static GLSL_VERSIONS: [uint, ..4] = [120, 130, 140, 150];
+
+/// An error encountered while loading or linking a program.
+pub struct ProgramError {
+    /// The name of the program, e.g. `"shader"`.
+    pub name: String,
+    pub desc: String,
+    /// The GLSL compiler or linker log, if the sources were read.
+    pub log: Option<String>
+}
+
+impl ProgramError {
+    fn new(name: &str, desc: String) -> ProgramError {
+        ProgramError {
+            name: name.to_string(),
+            desc: desc,
+            log: None
+        }
+    }
+}
+
+impl fmt::Show for ProgramError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "program '{}': {}", self.name, self.desc));
+        match self.log {
+            Some(ref log) => write!(f, "\n{}", log),
+            None => Ok(())
+        }
+    }
+}
+
+/// The sources of one shader stage, one per GLSL version.
+struct StageSources {
+    sources: Vec<Option<String>>
+}
+
+impl StageSources {
+    /// Reads `<name>.<ext>` and `<name>.<version>.<ext>`, each from
+    /// the last mod that has it or the base directory. The version of
+    /// the unversioned file is taken from its `#version` directive;
+    /// versioned files take precedence.
+    fn load(assets: &Assets, name: &str, ext: &str) -> Result<StageSources, ProgramError> {
+        let mut sources = Vec::from_elem(GLSL_VERSIONS.len(), None);
+
+        let path = assets.resolve(format!("{}.{}", name, ext).as_slice());
+        if path.exists() {
+            let src = try!(read_source(name, &path));
+            let version = glsl_version(src.as_slice()).unwrap_or(150);
+            match GLSL_VERSIONS.iter().position(|&v| v == version) {
+                Some(i) => *sources.get_mut(i) = Some(src),
+                None => return Err(ProgramError::new(name,
+                    format!("{} has unsupported GLSL version {}", path.display(), version)))
+            }
+        }
+
+        for (i, version) in GLSL_VERSIONS.iter().enumerate() {
+            let path = assets.resolve(format!("{}.{}.{}", name, version, ext).as_slice());
+            if path.exists() {
+                *sources.get_mut(i) = Some(try!(read_source(name, &path)));
+            }
+        }
+
+        if sources.iter().all(|s| s.is_none()) {
+            return Err(ProgramError::new(name,
+                format!("no .{} source in {} or its mods", ext, assets.base.display())));
+        }
+        Ok(StageSources { sources: sources })
+    }
+
+    fn shader_source<'a>(&'a self) -> gfx::ShaderSource<'a> {
+        let s = |i: uint| self.sources[i].as_ref().map(|src| src.as_bytes());
+        gfx::ShaderSource {
+            glsl_120: s(0),
+            glsl_130: s(1),
+            glsl_140: s(2),
+            glsl_150: s(3)
+        }
+    }
+}
+
+/// A linked shader program, loaded by name from `<name>.vert` and
+/// `<name>.frag` sources resolved through `Assets`.
 pub struct Program {
-    pub handle: device::Handle<u32, device::shade::ProgramInfo>
+    pub name: String,
+    pub handle: device::Handle<u32, device::shade::ProgramInfo>,
+    /// Every path a source file could be read from, including missing
+    /// ones, so a source added by a mod is noticed too.
+    watched: Vec<Path>,
+    /// The modification times of `watched` when last linked.
+    stamps: Vec<Option<u64>>
 }
 
 impl Program {
-    pub fn new<D: Device<C>, C: CommandBuffer>(r: &mut Renderer<D, C>, _filename: &str) -> Program {
-        let vert = match load_shader_source("data/shader.vert") {
-            Ok(src) => src,
-            Err(err) => panic!(err.desc)
-        };
-        let vert_src = gfx::ShaderSource {
-            glsl_120: None,
-            glsl_130: None,
-            glsl_140: None,
-            glsl_150: Some(vert.as_bytes())
-        };
-        let frag = match load_shader_source("data/shader.frag") {
-            Ok(src) => src,
-            Err(err) => panic!(err.desc)
-        };
-        let frag_src = gfx::ShaderSource {
-            glsl_120: None,
-            glsl_130: None,
-            glsl_140: None,
-            glsl_150: Some(frag.as_bytes())
-        };
-
-        Program {
-            handle: r.graphics.device.link_program(vert_src, frag_src).unwrap()
+    /// Loads and links the program `name`.
+    pub fn load<D: Device<C>, C: CommandBuffer>(device: &mut D, assets: &Assets, name: &str)
+                                                -> Result<Program, ProgramError> {
+        let mut watched = Vec::new();
+        for ext in ["vert", "frag"].iter() {
+            watched.push(format!("{}.{}", name, ext));
+            for version in GLSL_VERSIONS.iter() {
+                watched.push(format!("{}.{}.{}", name, version, ext));
+            }
         }
+        let watched: Vec<Path> = watched.iter()
+            .flat_map(|file| assets.candidates(file.as_slice()).into_iter())
+            .collect();
+
+        let stamps = stamps(watched.as_slice());
+        let handle = try!(link(device, assets, name));
+        Ok(Program {
+            name: name.to_string(),
+            handle: handle,
+            watched: watched,
+            stamps: stamps
+        })
+    }
+
+    /// Whether a source file was changed, added or removed since the
+    /// program was linked.
+    pub fn is_stale(&self) -> bool {
+        stamps(self.watched.as_slice()) != self.stamps
+    }
+
+    /// Links the program again from its sources and returns the new
+    /// handle. `handle` stays in use until it is swapped with `replace`
+    /// once every batch was remade with the new one.
+    pub fn relink<D: Device<C>, C: CommandBuffer>(&mut self, device: &mut D, assets: &Assets)
+                                                  -> Result<device::Handle<u32, device::shade::ProgramInfo>,
+                                                            ProgramError> {
+        // Don't retry a broken source until it changes again.
+        self.stamps = stamps(self.watched.as_slice());
+        link(device, assets, self.name.as_slice())
     }
+
+    /// Makes `handle` the program's handle and returns the previous one,
+    /// for the caller to delete.
+    pub fn replace(&mut self, handle: device::Handle<u32, device::shade::ProgramInfo>)
+                   -> device::Handle<u32, device::shade::ProgramInfo> {
+        mem::replace(&mut self.handle, handle)
+    }
+}
+
+fn link<D: Device<C>, C: CommandBuffer>(device: &mut D, assets: &Assets, name: &str)
+        -> Result<device::Handle<u32, device::shade::ProgramInfo>, ProgramError> {
+    let vert = try!(StageSources::load(assets, name, "vert"));
+    let frag = try!(StageSources::load(assets, name, "frag"));
+
+    device.link_program(vert.shader_source(), frag.shader_source()).map_err(|e| ProgramError {
+        name: name.to_string(),
+        desc: "could not compile or link".to_string(),
+        log: Some(format!("{}", e))
+    })
+}
+
+fn read_source(name: &str, path: &Path) -> Result<String, ProgramError> {
+    File::open(path).and_then(|mut f| f.read_to_string()).map_err(|e| {
+        ProgramError::new(name, format!("could not read {}: {}", path.display(), e))
+    })
+}
+
+/// Returns the version of a `#version` directive on the first
+/// non-empty line of `src`.
+fn glsl_version(src: &str) -> Option<uint> {
+    src.lines()
+        .map(|l| l.trim())
+        .find(|l| !l.is_empty())
+        .and_then(|l| {
+            let mut words = l.words();
+            match (words.next(), words.next()) {
+                (Some("#version"), Some(v)) => from_str(v),
+                _ => None
+            }
+        })
 }
 
-fn load_shader_source(filename: &str) -> IoResult<String> {
-    let mut f = match File::open(&Path::new(filename)) {
-        Err(why) => panic!("Could not open {}: {}", filename, why.desc),
-        Ok(file) => file
-    };
-    f.read_to_string()
+/// Returns the modification time of each of `paths`, `None` for the
+/// missing ones.
+fn stamps(paths: &[Path]) -> Vec<Option<u64>> {
+    paths.iter().map(|p| p.stat().ok().map(|s| s.modified)).collect()
 }
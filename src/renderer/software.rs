@@ -0,0 +1,320 @@
+use std::vec::Vec;
+use std::io::{File, IoResult, IoError, OtherIoError};
+use std::num::Float;
+use piston::image;
+use piston::image::{GenericImage, ImageBuf, Rgba};
+
+use super::{Vertex, mat4_mul, mat4_transform};
+
+/// A CPU rasterizer drawing the same vertex data as `Renderer`.
+///
+/// It mirrors the block shader: triangles are transformed by
+/// `projection * view`, clipped against the near plane, depth tested
+/// with `LessEqual` and coloured by the texture, sampled with wrapping
+/// at the nearest texel, times the vertex colour. It does not need a
+/// GPU, so it can render map snapshots on headless machines.
+pub struct SoftwareRenderer {
+    pub width: uint,
+    pub height: uint,
+    pub clear_color: Rgba<u8>,
+    color: Vec<Rgba<u8>>,
+    depth: Vec<f32>
+}
+
+/// A vertex in clip space, before the perspective divide.
+#[deriving(Clone)]
+struct ClipVertex {
+    pos: [f32, ..4],
+    uv: [f32, ..2],
+    color: [f32, ..3]
+}
+
+/// A vertex after the perspective divide, in window coordinates.
+struct ScreenVertex {
+    x: f32,
+    y: f32,
+    z: f32,
+    /// `1 / w`, for perspective correct interpolation.
+    inv_w: f32,
+    uv: [f32, ..2],
+    color: [f32, ..3]
+}
+
+impl SoftwareRenderer {
+    pub fn new(width: uint, height: uint) -> SoftwareRenderer {
+        let clear_color = Rgba(13, 0, 15, 255);
+        SoftwareRenderer {
+            width: width,
+            height: height,
+            clear_color: clear_color,
+            color: Vec::from_elem(width * height, clear_color),
+            depth: Vec::from_elem(width * height, 1.0)
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for c in self.color.iter_mut() {
+            *c = self.clear_color;
+        }
+        for d in self.depth.iter_mut() {
+            *d = 1.0;
+        }
+    }
+
+    /// Draws the triangle list `indices` into `vertices`.
+    pub fn draw(&mut self, vertices: &[Vertex], indices: &[u32],
+                projection: [[f32, ..4], ..4], view: [[f32, ..4], ..4],
+                texture: &ImageBuf<Rgba<u8>>) {
+        let mvp = mat4_mul(projection, view);
+        let clip: Vec<ClipVertex> = vertices.iter().map(|v| {
+            ClipVertex {
+                pos: mat4_transform(mvp, [v.pos[0], v.pos[1], v.pos[2], 1.0]),
+                uv: v.uv,
+                color: v.color
+            }
+        }).collect();
+
+        for tri in indices.chunks(3) {
+            if tri.len() < 3 {
+                break;
+            }
+            let poly = clip_near([clip[tri[0] as uint].clone(),
+                                  clip[tri[1] as uint].clone(),
+                                  clip[tri[2] as uint].clone()]);
+            if poly.len() < 3 {
+                continue;
+            }
+            let screen: Vec<ScreenVertex> = poly.iter().map(|v| self.to_screen(v)).collect();
+            for i in range(1, screen.len() - 1) {
+                self.fill(&screen[0], &screen[i], &screen[i + 1], texture);
+            }
+        }
+    }
+
+    pub fn to_image(&self) -> ImageBuf<Rgba<u8>> {
+        ImageBuf::from_fn(self.width as u32, self.height as u32, |x, y| {
+            self.color[y as uint * self.width + x as uint]
+        })
+    }
+
+    /// Saves the colour buffer as a PNG.
+    pub fn save(&self, path: &Path) -> IoResult<()> {
+        let fout = try!(File::create(path));
+        match image::ImageRgba8(self.to_image()).save(fout, image::PNG) {
+            Ok(()) => Ok(()),
+            Err(why) => Err(IoError {
+                kind: OtherIoError,
+                desc: "Could not encode PNG",
+                detail: Some(format!("{}: {}", path.display(), why))
+            })
+        }
+    }
+
+    /// Divides clipped vertex `v` by its `w`, which the near plane
+    /// keeps positive.
+    fn to_screen(&self, v: &ClipVertex) -> ScreenVertex {
+        let p = v.pos;
+        let inv_w = 1.0 / p[3];
+        ScreenVertex {
+            x: (p[0] * inv_w + 1.0) * 0.5 * self.width as f32,
+            y: (1.0 - p[1] * inv_w) * 0.5 * self.height as f32,
+            z: (p[2] * inv_w + 1.0) * 0.5,
+            inv_w: inv_w,
+            uv: v.uv,
+            color: v.color
+        }
+    }
+
+    fn fill(&mut self, a: &ScreenVertex, b: &ScreenVertex, c: &ScreenVertex,
+            texture: &ImageBuf<Rgba<u8>>) {
+        let area = edge(a, b, c.x, c.y);
+        if area == 0.0 {
+            return;
+        }
+
+        let (width, height) = (self.width as f32, self.height as f32);
+        let min_x = a.x.min(b.x).min(c.x).floor().max(0.0).min(width) as uint;
+        let min_y = a.y.min(b.y).min(c.y).floor().max(0.0).min(height) as uint;
+        let max_x = a.x.max(b.x).max(c.x).ceil().max(0.0).min(width) as uint;
+        let max_y = a.y.max(b.y).max(c.y).ceil().max(0.0).min(height) as uint;
+        if min_x >= max_x || min_y >= max_y {
+            return;
+        }
+
+        let (tw, th) = texture.dimensions();
+        for y in range(min_y, max_y) {
+            for x in range(min_x, max_x) {
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                let w0 = edge(b, c, px, py) / area;
+                let w1 = edge(c, a, px, py) / area;
+                let w2 = edge(a, b, px, py) / area;
+                if w0 < 0.0 || w1 < 0.0 || w2 < 0.0 {
+                    continue;
+                }
+
+                let z = w0 * a.z + w1 * b.z + w2 * c.z;
+                let i = y * self.width + x;
+                if z < 0.0 || z > self.depth[i] {
+                    continue;
+                }
+
+                let inv_w = w0 * a.inv_w + w1 * b.inv_w + w2 * c.inv_w;
+                let lerp = |fa: f32, fb: f32, fc: f32| {
+                    (w0 * fa * a.inv_w + w1 * fb * b.inv_w + w2 * fc * c.inv_w) / inv_w
+                };
+                let u = lerp(a.uv[0], b.uv[0], c.uv[0]);
+                let v = lerp(a.uv[1], b.uv[1], c.uv[1]);
+                let Rgba(r, g, bl, al) = texture.get_pixel(wrap(u, tw), wrap(v, th));
+
+                *self.depth.get_mut(i) = z;
+                *self.color.get_mut(i) = Rgba(
+                    modulate(r, lerp(a.color[0], b.color[0], c.color[0])),
+                    modulate(g, lerp(a.color[1], b.color[1], c.color[1])),
+                    modulate(bl, lerp(a.color[2], b.color[2], c.color[2])),
+                    al);
+            }
+        }
+    }
+}
+
+impl ClipVertex {
+    /// Returns the vertex a fraction `t` of the way to `other`.
+    fn lerp(&self, other: &ClipVertex, t: f32) -> ClipVertex {
+        let mix = |a: f32, b: f32| a + (b - a) * t;
+        ClipVertex {
+            pos: [mix(self.pos[0], other.pos[0]), mix(self.pos[1], other.pos[1]),
+                  mix(self.pos[2], other.pos[2]), mix(self.pos[3], other.pos[3])],
+            uv: [mix(self.uv[0], other.uv[0]), mix(self.uv[1], other.uv[1])],
+            color: [mix(self.color[0], other.color[0]), mix(self.color[1], other.color[1]),
+                    mix(self.color[2], other.color[2])]
+        }
+    }
+}
+
+/// Clips triangle `tri` against the near plane `z = -w`. Returns the
+/// corners of the part in front of it: none, three or four.
+fn clip_near(tri: [ClipVertex, ..3]) -> Vec<ClipVertex> {
+    let dist = |v: &ClipVertex| v.pos[2] + v.pos[3];
+    let mut poly = Vec::with_capacity(4);
+    for i in range(0, 3u) {
+        let (a, b) = (&tri[i], &tri[(i + 1) % 3]);
+        let (da, db) = (dist(a), dist(b));
+        if da >= 0.0 {
+            poly.push(a.clone());
+        }
+        if (da >= 0.0) != (db >= 0.0) {
+            poly.push(a.lerp(b, da / (da - db)));
+        }
+    }
+    poly
+}
+
+/// Returns twice the signed area of the triangle `a`, `b`, `(x, y)`.
+fn edge(a: &ScreenVertex, b: &ScreenVertex, x: f32, y: f32) -> f32 {
+    (b.x - a.x) * (y - a.y) - (b.y - a.y) * (x - a.x)
+}
+
+/// Multiplies colour component `c` by `f`.
+fn modulate(c: u8, f: f32) -> u8 {
+    (c as f32 * f).max(0.0).min(255.0) as u8
+}
+
+/// Maps texture coordinate `t` to a texel, repeating the texture.
+fn wrap(t: f32, size: u32) -> u32 {
+    let t = t - t.floor();
+    ((t * size as f32) as u32).min(size - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use piston::image::{GenericImage, ImageBuf, Rgba};
+
+    use renderer::Vertex;
+    use super::SoftwareRenderer;
+
+    static IDENTITY: [[f32, ..4], ..4] = [[1.0, 0.0, 0.0, 0.0],
+                                          [0.0, 1.0, 0.0, 0.0],
+                                          [0.0, 0.0, 1.0, 0.0],
+                                          [0.0, 0.0, 0.0, 1.0]];
+
+    /// A perspective projection with a 90 degree field of view, a
+    /// square aspect and clip planes at 0.1 and 100.
+    fn perspective() -> [[f32, ..4], ..4] {
+        let (near, far) = (0.1, 100.0);
+        [[1.0, 0.0, 0.0, 0.0],
+         [0.0, 1.0, 0.0, 0.0],
+         [0.0, 0.0, (far + near) / (near - far), -1.0],
+         [0.0, 0.0, 2.0 * far * near / (near - far), 0.0]]
+    }
+
+    fn vertex(pos: [f32, ..3], uv: [f32, ..2]) -> Vertex {
+        Vertex::new(pos, uv, [1.0, 1.0, 1.0])
+    }
+
+    fn assert_same(actual: &ImageBuf<Rgba<u8>>, expected: &ImageBuf<Rgba<u8>>) {
+        assert_eq!(actual.dimensions(), expected.dimensions());
+        let (w, h) = actual.dimensions();
+        for y in range(0, h) {
+            for x in range(0, w) {
+                let Rgba(r0, g0, b0, a0) = actual.get_pixel(x, y);
+                let Rgba(r1, g1, b1, a1) = expected.get_pixel(x, y);
+                assert!((r0, g0, b0, a0) == (r1, g1, b1, a1),
+                        "pixel ({}, {}) is {} but should be {}",
+                        x, y, (r0, g0, b0, a0), (r1, g1, b1, a1));
+            }
+        }
+    }
+
+    #[test]
+    fn draws_a_textured_quad() {
+        let colors = [Rgba(255, 0, 0, 255), Rgba(0, 255, 0, 255),
+                      Rgba(0, 0, 255, 255), Rgba(255, 255, 255, 255)];
+        let texture = ImageBuf::from_fn(2, 2, |x, y| colors[(y * 2 + x) as uint]);
+        let quad = [vertex([-1.0, 1.0, 0.0], [0.0, 0.0]),
+                    vertex([1.0, 1.0, 0.0], [1.0, 0.0]),
+                    vertex([-1.0, -1.0, 0.0], [0.0, 1.0]),
+                    vertex([1.0, -1.0, 0.0], [1.0, 1.0])];
+
+        let mut r = SoftwareRenderer::new(4, 4);
+        r.draw(quad.as_slice(), &[0, 1, 2, 1, 3, 2], IDENTITY, IDENTITY, &texture);
+
+        // Each texel covers a quarter of the image.
+        let expected = ImageBuf::from_fn(4, 4, |x, y| colors[(y / 2 * 2 + x / 2) as uint]);
+        assert_same(&r.to_image(), &expected);
+    }
+
+    #[test]
+    fn skips_triangles_off_the_top_left() {
+        let texture = ImageBuf::from_pixel(1, 1, Rgba(255, 0, 0, 255));
+        let tri = [vertex([-3.0, 3.0, 0.0], [0.0, 0.0]),
+                   vertex([-2.0, 3.0, 0.0], [0.0, 0.0]),
+                   vertex([-3.0, 2.0, 0.0], [0.0, 0.0])];
+
+        let mut r = SoftwareRenderer::new(4, 4);
+        r.draw(tri.as_slice(), &[0, 1, 2], IDENTITY, IDENTITY, &texture);
+
+        let expected = ImageBuf::from_pixel(4, 4, r.clear_color);
+        assert_same(&r.to_image(), &expected);
+    }
+
+    #[test]
+    fn clips_triangles_crossing_the_near_plane() {
+        let red = Rgba(255, 0, 0, 255);
+        let texture = ImageBuf::from_pixel(1, 1, red);
+        // A floor below the eye, reaching from far ahead to behind it.
+        let floor = [vertex([-50.0, -1.0, -50.0], [0.0, 0.0]),
+                     vertex([50.0, -1.0, -50.0], [0.0, 0.0]),
+                     vertex([0.0, -1.0, 5.0], [0.0, 0.0])];
+
+        let mut r = SoftwareRenderer::new(8, 8);
+        r.draw(floor.as_slice(), &[0, 1, 2], perspective(), IDENTITY, &texture);
+
+        let img = r.to_image();
+        let Rgba(r0, g0, b0, a0) = img.get_pixel(4, 7);
+        assert_eq!((r0, g0, b0, a0), (255, 0, 0, 255));
+        // The sky above the horizon is left clear.
+        let Rgba(r1, g1, b1, a1) = img.get_pixel(4, 0);
+        let Rgba(r2, g2, b2, a2) = r.clear_color;
+        assert_eq!((r1, g1, b1, a1), (r2, g2, b2, a2));
+    }
+}
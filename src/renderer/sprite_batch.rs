@@ -0,0 +1,239 @@
+use std::cmp;
+use std::cmp::Equal;
+use std::num::Float;
+
+use piston::image::{GenericImage, ImageBuf, Rgba};
+
+use style::Style;
+use style::vehicle_spec::PIXELS_PER_BLOCK;
+use super::Vertex;
+
+/// The width of a sprite atlas in pixels, unless the device can't
+/// make textures that wide.
+pub static ATLAS_WIDTH: u32 = 2048;
+
+/// The height sprites are drawn above the surface they lie on, so
+/// they don't fight with the lids below them.
+static LIFT: f32 = 0.01;
+
+/// A rectangle of an atlas in texture coordinates.
+#[deriving(Clone, Show)]
+pub struct Region {
+    pub uv_min: [f32, ..2],
+    pub uv_max: [f32, ..2],
+    /// The size in pixels.
+    pub width: u32,
+    pub height: u32
+}
+
+/// Decoded sprites packed into one image, to be drawn in one batch.
+pub struct SpriteAtlas {
+    pub image: ImageBuf<Rgba<u8>>,
+    pub regions: Vec<Region>,
+    /// Where the next sprite goes.
+    cursor: [u32, ..2],
+    /// The height of the current row.
+    row_height: u32
+}
+
+impl SpriteAtlas {
+    /// Creates an empty atlas of `width` by `height` pixels.
+    pub fn new(width: u32, height: u32) -> SpriteAtlas {
+        SpriteAtlas {
+            image: ImageBuf::from_pixel(width, height, Rgba(0, 0, 0, 0)),
+            regions: Vec::new(),
+            cursor: [0, 0],
+            row_height: 0
+        }
+    }
+
+    /// Creates an atlas of every sprite in `style` with its own
+    /// palette. Region `n` is absolute sprite number `n`.
+    pub fn from_style(style: &Style, max_size: u32) -> Result<SpriteAtlas, String> {
+        let mut images = Vec::with_capacity(style.sprite_info.len());
+        for n in range(0, style.sprite_info.len()) {
+            images.push(try!(style.sprite_image(n)));
+        }
+        SpriteAtlas::from_images(images.as_slice(), max_size)
+    }
+
+    /// Creates an atlas just big enough for `images`. Region `n` is
+    /// `images[n]`. Neither side may exceed `max_size`, usually the
+    /// largest texture the device supports; the width is narrowed to
+    /// fit and the atlas fails if it is still too tall.
+    pub fn from_images(images: &[ImageBuf<Rgba<u8>>], max_size: u32)
+                       -> Result<SpriteAtlas, String> {
+        let width = cmp::min(ATLAS_WIDTH, max_size);
+        let height = atlas_height(images, width);
+        if height > max_size {
+            return Err(format!("Sprite atlas of {}x{} exceeds the maximum size of {}",
+                               width, height, max_size));
+        }
+        let mut atlas = SpriteAtlas::new(width, height);
+        for img in images.iter() {
+            try!(atlas.add(img));
+        }
+        Ok(atlas)
+    }
+
+    /// Copies `img` into the atlas and returns its region number.
+    /// Sprites are packed left to right in rows.
+    pub fn add(&mut self, img: &ImageBuf<Rgba<u8>>) -> Result<uint, String> {
+        let (w, h) = img.dimensions();
+        let (atlas_w, atlas_h) = self.image.dimensions();
+        if w > atlas_w {
+            return Err(format!("Sprite of width {} does not fit the atlas", w));
+        }
+        if self.cursor[0] + w > atlas_w {
+            self.cursor = [0, self.cursor[1] + self.row_height];
+            self.row_height = 0;
+        }
+        if self.cursor[1] + h > atlas_h {
+            return Err("Sprite atlas is full".to_string());
+        }
+
+        let (x0, y0) = (self.cursor[0], self.cursor[1]);
+        for y in range(0, h) {
+            for x in range(0, w) {
+                self.image.put_pixel(x0 + x, y0 + y, img.get_pixel(x, y));
+            }
+        }
+
+        self.regions.push(Region {
+            uv_min: [x0 as f32 / atlas_w as f32, y0 as f32 / atlas_h as f32],
+            uv_max: [(x0 + w) as f32 / atlas_w as f32, (y0 + h) as f32 / atlas_h as f32],
+            width: w,
+            height: h
+        });
+        self.cursor[0] += w;
+        if h > self.row_height {
+            self.row_height = h;
+        }
+        Ok(self.regions.len() - 1)
+    }
+}
+
+/// Returns the atlas height needed to pack `images` in rows of
+/// `width` pixels.
+fn atlas_height(images: &[ImageBuf<Rgba<u8>>], width: u32) -> u32 {
+    let (mut x, mut y, mut row_height) = (0u32, 0u32, 0u32);
+    for img in images.iter() {
+        let (w, h) = img.dimensions();
+        if x + w > width {
+            x = 0;
+            y += row_height;
+            row_height = 0;
+        }
+        x += w;
+        if h > row_height {
+            row_height = h;
+        }
+    }
+    y + row_height
+}
+
+/// A sprite lying flat in the world, seen from above.
+#[deriving(Clone, Show)]
+pub struct Sprite {
+    /// The centre of the sprite in world coordinates.
+    pub pos: [f32, ..3],
+    /// The rotation around the vertical axis in radians.
+    pub rotation: f32,
+    pub scale: f32,
+    /// The atlas region to draw.
+    pub region: uint
+}
+
+/// Collects sprites for a frame and turns them into the vertices of
+/// one depth-sorted batch.
+///
+/// The batch holds up to `capacity` sprites. Its indices never change,
+/// unused quads are collapsed to a point, so a GPU buffer made from
+/// `indices` only needs its vertices updated each frame.
+pub struct SpriteBatch {
+    pub sprites: Vec<Sprite>,
+    capacity: uint
+}
+
+impl SpriteBatch {
+    pub fn new(capacity: uint) -> SpriteBatch {
+        SpriteBatch {
+            sprites: Vec::with_capacity(capacity),
+            capacity: capacity
+        }
+    }
+
+    pub fn capacity(&self) -> uint {
+        self.capacity
+    }
+
+    /// Queues `sprite` for this frame. Sprites beyond the capacity
+    /// are dropped.
+    pub fn push(&mut self, sprite: Sprite) {
+        if self.sprites.len() < self.capacity {
+            self.sprites.push(sprite);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.sprites.clear();
+    }
+
+    /// Returns the indices of a full batch, two triangles per quad.
+    pub fn indices(&self) -> Vec<u32> {
+        let mut indices = Vec::with_capacity(self.capacity * 6);
+        for n in range(0, self.capacity as u32) {
+            let i = n * 4;
+            indices.push_all([i, i + 1, i + 2, i, i + 2, i + 3]);
+        }
+        indices
+    }
+
+    /// Sorts the queued sprites back to front as seen from `eye` and
+    /// returns the vertices of the whole batch.
+    pub fn vertices(&mut self, atlas: &SpriteAtlas, eye: [f32, ..3]) -> Vec<Vertex> {
+        let dist = |s: &Sprite| {
+            let d = [s.pos[0] - eye[0], s.pos[1] - eye[1], s.pos[2] - eye[2]];
+            d[0] * d[0] + d[1] * d[1] + d[2] * d[2]
+        };
+        // Sprites at NaN positions are left wherever the sort puts them.
+        self.sprites.sort_by(|a, b| dist(b).partial_cmp(&dist(a)).unwrap_or(Equal));
+
+        let mut verts = Vec::with_capacity(self.capacity * 4);
+        for sprite in self.sprites.iter() {
+            if sprite.region >= atlas.regions.len() {
+                continue;
+            }
+            verts.push_all(quad(sprite, &atlas.regions[sprite.region]).as_slice());
+        }
+
+        let empty = Vertex::new([0.0, ..3], [0.0, ..2], [0.0, ..3]);
+        while verts.len() < self.capacity * 4 {
+            verts.push(empty);
+        }
+        verts
+    }
+}
+
+/// Returns the corners of `sprite` drawn with `region`, counter
+/// clockwise seen from above.
+fn quad(sprite: &Sprite, region: &Region) -> [Vertex, ..4] {
+    let half_w = region.width as f32 / PIXELS_PER_BLOCK * sprite.scale / 2.0;
+    let half_h = region.height as f32 / PIXELS_PER_BLOCK * sprite.scale / 2.0;
+    let (sin, cos) = sprite.rotation.sin_cos();
+    let corner = |x: f32, z: f32, u: f32, v: f32| {
+        Vertex::new([sprite.pos[0] + x * cos - z * sin,
+                     sprite.pos[1] + LIFT,
+                     sprite.pos[2] + x * sin + z * cos],
+                    [u, v],
+                    [1.0, 1.0, 1.0])
+    };
+    let (u0, v0) = (region.uv_min[0], region.uv_min[1]);
+    let (u1, v1) = (region.uv_max[0], region.uv_max[1]);
+    // The world x axis runs against the map's, so the sprite's left
+    // edge is at +x.
+    [corner(half_w, -half_h, u0, v0),
+     corner(half_w, half_h, u0, v1),
+     corner(-half_w, half_h, u1, v1),
+     corner(-half_w, -half_h, u1, v0)]
+}
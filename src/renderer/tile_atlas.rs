@@ -0,0 +1,87 @@
+use piston::image::{GenericImage, ImageBuf, Rgba};
+
+use style::{Style, TILE_SIZE};
+
+/// The number of tiles in a row of the atlas.
+static TILES_PER_ROW: uint = 32;
+
+/// The tiles of a style decoded into one texture, with lid remap 0.
+///
+/// Faces of blocks refer to tiles by number: side faces to the side
+/// tiles, lids to the lid tiles that follow them. 0 means no face.
+pub struct TileAtlas {
+    pub image: ImageBuf<Rgba<u8>>,
+    num_side_tiles: uint,
+    num_lid_tiles: uint,
+    /// Whether each tile has see-through pixels, colour index 0.
+    transparent: Vec<bool>
+}
+
+impl TileAtlas {
+    pub fn from_style(style: &Style) -> Result<TileAtlas, String> {
+        let num_tiles = style.num_tiles();
+        let rows = (num_tiles + TILES_PER_ROW - 1) / TILES_PER_ROW;
+        let mut image = ImageBuf::from_pixel((TILES_PER_ROW * TILE_SIZE) as u32,
+                                             (rows * TILE_SIZE) as u32,
+                                             Rgba(0, 0, 0, 0));
+        let mut transparent = Vec::with_capacity(num_tiles);
+
+        for tile in range(0, num_tiles) {
+            let img = try!(style.tile_image(tile, 0));
+            let x0 = (tile % TILES_PER_ROW * TILE_SIZE) as u32;
+            let y0 = (tile / TILES_PER_ROW * TILE_SIZE) as u32;
+            let mut see_through = false;
+            for y in range(0, TILE_SIZE as u32) {
+                for x in range(0, TILE_SIZE as u32) {
+                    let pixel = img.get_pixel(x, y);
+                    let Rgba(_, _, _, a) = pixel;
+                    see_through = see_through || a == 0;
+                    image.put_pixel(x0 + x, y0 + y, pixel);
+                }
+            }
+            transparent.push(see_through);
+        }
+
+        Ok(TileAtlas {
+            image: image,
+            num_side_tiles: style.num_side_tiles,
+            num_lid_tiles: style.num_lid_tiles,
+            transparent: transparent
+        })
+    }
+
+    /// Returns the tile of side face `face`, if there is one.
+    pub fn side(&self, face: u8) -> Option<uint> {
+        let face = face as uint;
+        if face == 0 || face >= self.num_side_tiles { None } else { Some(face) }
+    }
+
+    /// Returns the tile of lid face `face`, if there is one.
+    pub fn lid(&self, face: u8) -> Option<uint> {
+        let face = face as uint;
+        if face == 0 || face >= self.num_lid_tiles {
+            None
+        } else {
+            Some(self.num_side_tiles + face)
+        }
+    }
+
+    /// Whether `tile` has see-through pixels, which are only drawn
+    /// see-through on flat blocks.
+    pub fn is_transparent(&self, tile: uint) -> bool {
+        tile < self.transparent.len() && self.transparent[tile]
+    }
+
+    /// Returns the texture coordinates of the corners of `tile`, in
+    /// the order top left, top right, bottom left, bottom right.
+    pub fn tex_coords(&self, tile: uint) -> [[f32, ..2], ..4] {
+        let (w, h) = self.image.dimensions();
+        // Inset by half a texel so filtering doesn't bleed in the
+        // neighbouring tiles.
+        let (x0, y0) = ((tile % TILES_PER_ROW * TILE_SIZE) as f32 + 0.5,
+                        (tile / TILES_PER_ROW * TILE_SIZE) as f32 + 0.5);
+        let (x1, y1) = (x0 + TILE_SIZE as f32 - 1.0, y0 + TILE_SIZE as f32 - 1.0);
+        let (w, h) = (w as f32, h as f32);
+        [[x0 / w, y0 / h], [x1 / w, y0 / h], [x0 / w, y1 / h], [x1 / w, y1 / h]]
+    }
+}
@@ -1,3 +1,4 @@
+#[deriving(Clone, Encodable, Decodable)]
 pub struct Animation {
     /// The block number.
     pub block: u8,
@@ -8,6 +9,7 @@ pub struct Animation {
     pub frames: Vec<u8>
 }
 
+#[deriving(Clone, Encodable, Decodable)]
 pub enum AreaType {
     Side = 0,
     Lid = 1
@@ -1,5 +1,12 @@
 use super::hls_info::HlsInfo;
+use super::vehicle_spec::VehicleSpec;
 
+/// The number of HLS and of palette remaps of a car.
+pub static NUM_REMAPS: uint = 12;
+/// The number of crane values of a car.
+pub static NUM_VALUES: uint = 4;
+
+#[deriving(Clone, Encodable, Decodable)]
 pub struct CarInfo {
     pub width: i16,
     pub height: i16,
@@ -19,9 +26,11 @@ pub struct CarInfo {
     pub grip: i16,
     pub handling: i16,
 
-    pub remap24: [HlsInfo, ..12],
+    /// The 12 HLS remaps of the car.
+    pub remap24: Vec<HlsInfo>,
 
-    pub remap8: [u8, ..12],
+    /// The 12 palette remaps of the car.
+    pub remap8: Vec<u8>,
     pub vehicle_type: VehicleType,
     /// A sub-type within `vehicle_type` for cars which hold an
     /// identifier for the model of car.
@@ -31,7 +40,7 @@ pub struct CarInfo {
     pub damageable: u8,
     /// The monetary value of the car in the GTA mission, in 1000s of
     /// $. There are 4 value entries for the 4 cranes.
-    pub value: [u16, ..4],
+    pub value: Vec<u16>,
 
     // The pixel co-ordinates of the centre of mass of the car,
     // relative to the graphical centre.
@@ -78,7 +87,27 @@ pub struct CarInfo {
     pub doors: Vec<Door>
 }
 
-#[deriving(Show)]
+impl CarInfo {
+    /// Returns the car's handling in SI units.
+    pub fn spec(&self) -> VehicleSpec {
+        VehicleSpec::from_car_info(self)
+    }
+
+    /// Checks that the remap and value lists have the lengths of the
+    /// style format.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.remap24.len() != NUM_REMAPS || self.remap8.len() != NUM_REMAPS {
+            return Err(format!("{} HLS and {} palette remaps, expected {} of each",
+                               self.remap24.len(), self.remap8.len(), NUM_REMAPS));
+        }
+        if self.value.len() != NUM_VALUES {
+            return Err(format!("{} values, expected {}", self.value.len(), NUM_VALUES));
+        }
+        Ok(())
+    }
+}
+
+#[deriving(Show, Clone, Encodable, Decodable)]
 pub enum VehicleType {
     Unknown,
     Bus,
@@ -103,6 +132,7 @@ impl VehicleType {
     }
 }
 
+#[deriving(Clone, Encodable, Decodable)]
 pub struct Door {
     /// Relative x-position.
     pub x: i16,
@@ -113,3 +143,13 @@ pub struct Door {
     /// TODO: Unknown
     pub delta: i16
 }
+
+/// Selects the colour variant of a car sprite.
+pub enum Remap {
+    /// The colours of the sprite's own palette.
+    NoRemap,
+    /// The sprite's own colours shifted by `remap24[n]`.
+    Remap24(uint),
+    /// The car remap palette referenced by `remap8[n]`.
+    Remap8(uint)
+}
@@ -0,0 +1,84 @@
+use std::vec::Vec;
+use piston::image::Rgba;
+
+/// The size of a CLUT page in bytes.
+pub static PAGE_SIZE: uint = 65536;
+/// The size of the palette of an 8-bit style: 256 RGB triplets.
+pub static VGA_PALETTE_SIZE: uint = 768;
+/// The number of palettes stored in a single page.
+static PALETTES_PER_PAGE: uint = 64;
+
+/// The colour lookup tables of a style.
+///
+/// CLUTs are stored in 64K pages of 64 palettes each. Within a page
+/// the colours are interleaved, so the first colour of every palette
+/// comes first, then the second colour of every palette and so on.
+pub struct Clut {
+    colors: Vec<Rgba<u8>>
+}
+
+impl Clut {
+    /// Decodes `num_palettes` palettes from paged CLUT `data`.
+    pub fn from_pages(data: &[u8], num_palettes: uint) -> Clut {
+        let mut colors = Vec::from_elem(num_palettes * 256, Rgba(0, 0, 0, 0));
+        for palette in range(0, num_palettes) {
+            let page = palette / PALETTES_PER_PAGE;
+            let column = palette % PALETTES_PER_PAGE;
+            for color in range(0, 256) {
+                let offset = page * PAGE_SIZE + color * 256 + column * 4;
+                // Colours are stored as BGRA.
+                *colors.get_mut(palette * 256 + color) = Rgba(data[offset + 2],
+                                                              data[offset + 1],
+                                                              data[offset],
+                                                              255);
+            }
+        }
+
+        Clut {
+            colors: colors
+        }
+    }
+
+    /// Builds the CLUT of an 8-bit style from its single `palette` of
+    /// 6-bit RGB triplets, `VGA_PALETTE_SIZE` bytes, and its 256-byte
+    /// colour `remaps` tables.
+    ///
+    /// Palette 0 is the palette itself, palette `n + 1` is the
+    /// palette seen through remap table `n`.
+    pub fn from_remaps(palette: &[u8], remaps: &[u8]) -> Clut {
+        let base: Vec<Rgba<u8>> = range(0, 256u).map(|i| {
+            Rgba(vga_to_rgb(palette[i * 3]),
+                 vga_to_rgb(palette[i * 3 + 1]),
+                 vga_to_rgb(palette[i * 3 + 2]),
+                 255)
+        }).collect();
+
+        let num_remaps = remaps.len() / 256;
+        let mut colors = Vec::with_capacity((num_remaps + 1) * 256);
+        colors.push_all(base.as_slice());
+        for remap in range(0, num_remaps) {
+            for color in range(0, 256) {
+                colors.push(base[remaps[remap * 256 + color] as uint]);
+            }
+        }
+
+        Clut {
+            colors: colors
+        }
+    }
+
+    /// Returns the number of palettes.
+    pub fn len(&self) -> uint {
+        self.colors.len() / 256
+    }
+
+    /// Returns colour `index` of physical palette `palette`.
+    pub fn color(&self, palette: uint, index: u8) -> Rgba<u8> {
+        self.colors[palette * 256 + index as uint]
+    }
+}
+
+/// Scales a 6-bit VGA colour component to 8 bits.
+pub fn vga_to_rgb(c: u8) -> u8 {
+    (c & 63) << 2 | (c & 63) >> 4
+}
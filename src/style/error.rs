@@ -0,0 +1,41 @@
+use std::fmt;
+use std::io::IoError;
+
+pub type StyleResult<T> = Result<T, StyleError>;
+
+/// An error encountered while loading a style.
+pub struct StyleError {
+    /// The section being read, e.g. `"car info"`.
+    pub section: &'static str,
+    /// The file offset at which reading failed.
+    pub offset: uint,
+    pub desc: String,
+    /// The underlying I/O error, if any.
+    pub cause: Option<IoError>
+}
+
+impl StyleError {
+    pub fn new(section: &'static str, offset: uint, desc: String) -> StyleError {
+        StyleError {
+            section: section,
+            offset: offset,
+            desc: desc,
+            cause: None
+        }
+    }
+
+    pub fn from_io(section: &'static str, offset: uint, err: IoError) -> StyleError {
+        StyleError {
+            section: section,
+            offset: offset,
+            desc: format!("{}", err),
+            cause: Some(err)
+        }
+    }
+}
+
+impl fmt::Show for StyleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at offset {}: {}", self.section, self.offset, self.desc)
+    }
+}
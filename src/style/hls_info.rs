@@ -1,3 +1,9 @@
+use std::num::Float;
+use piston::image::Rgba;
+
+/// A hue, lightness and saturation shift used for remapping car
+/// colours. `h` is in degrees, `l` and `s` are in percent.
+#[deriving(Clone, Encodable, Decodable)]
 pub struct HlsInfo {
     pub h: i16,
     pub l: i16,
@@ -20,4 +26,111 @@ impl HlsInfo {
             s: 0
         }
     }
+
+    /// Returns `color` with the shift applied. Alpha is kept as is.
+    pub fn apply(&self, color: Rgba<u8>) -> Rgba<u8> {
+        let Rgba(r, g, b, a) = color;
+        let (h, l, s) = rgb_to_hls(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0);
+
+        let mut h = (h + self.h as f32) % 360.0;
+        if h < 0.0 {
+            h += 360.0;
+        }
+        let l = clamp(l + self.l as f32 / 100.0);
+        let s = clamp(s + self.s as f32 / 100.0);
+
+        let (r, g, b) = hls_to_rgb(h, l, s);
+        Rgba((r * 255.0).round() as u8,
+             (g * 255.0).round() as u8,
+             (b * 255.0).round() as u8,
+             a)
+    }
+}
+
+fn clamp(v: f32) -> f32 {
+    v.max(0.0).min(1.0)
+}
+
+/// Converts an RGB colour to hue in degrees and lightness and
+/// saturation in the range 0-1.
+fn rgb_to_hls(r: f32, g: f32, b: f32) -> (f32, f32, f32) {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let l = (max + min) / 2.0;
+
+    if max == min {
+        return (0.0, l, 0.0);
+    }
+
+    let d = max - min;
+    let s = if l > 0.5 { d / (2.0 - max - min) } else { d / (max + min) };
+    let h = if max == r {
+        (g - b) / d + if g < b { 6.0 } else { 0.0 }
+    } else if max == g {
+        (b - r) / d + 2.0
+    } else {
+        (r - g) / d + 4.0
+    };
+
+    (h * 60.0, l, s)
+}
+
+fn hls_to_rgb(h: f32, l: f32, s: f32) -> (f32, f32, f32) {
+    if s == 0.0 {
+        return (l, l, l);
+    }
+
+    let q = if l < 0.5 { l * (1.0 + s) } else { l + s - l * s };
+    let p = 2.0 * l - q;
+    let h = h / 360.0;
+
+    (hue_to_rgb(p, q, h + 1.0 / 3.0),
+     hue_to_rgb(p, q, h),
+     hue_to_rgb(p, q, h - 1.0 / 3.0))
+}
+
+fn hue_to_rgb(p: f32, q: f32, t: f32) -> f32 {
+    let t = if t < 0.0 { t + 1.0 } else if t > 1.0 { t - 1.0 } else { t };
+    if t < 1.0 / 6.0 {
+        p + (q - p) * 6.0 * t
+    } else if t < 1.0 / 2.0 {
+        q
+    } else if t < 2.0 / 3.0 {
+        p + (q - p) * (2.0 / 3.0 - t) * 6.0
+    } else {
+        p
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use piston::image::Rgba;
+
+    use super::HlsInfo;
+
+    fn apply(h: i16, l: i16, s: i16, color: (u8, u8, u8, u8)) -> (u8, u8, u8, u8) {
+        let (r, g, b, a) = color;
+        let Rgba(r, g, b, a) = HlsInfo::new(h, l, s).apply(Rgba(r, g, b, a));
+        (r, g, b, a)
+    }
+
+    #[test]
+    fn zero_shift_keeps_the_colour() {
+        assert_eq!(apply(0, 0, 0, (30, 20, 10, 255)), (30, 20, 10, 255));
+        assert_eq!(apply(0, 0, 0, (200, 200, 200, 7)), (200, 200, 200, 7));
+    }
+
+    #[test]
+    fn hue_wraps_both_ways() {
+        assert_eq!(apply(120, 0, 0, (255, 0, 0, 255)), (0, 255, 0, 255));
+        assert_eq!(apply(-120, 0, 0, (255, 0, 0, 255)), (0, 0, 255, 255));
+        assert_eq!(apply(360, 0, 0, (255, 0, 0, 255)), (255, 0, 0, 255));
+    }
+
+    #[test]
+    fn lightness_and_saturation_are_clamped() {
+        assert_eq!(apply(0, 100, 0, (255, 0, 0, 255)), (255, 255, 255, 255));
+        assert_eq!(apply(0, -100, 0, (255, 0, 0, 255)), (0, 0, 0, 255));
+        assert_eq!(apply(0, 0, -200, (255, 0, 0, 128)), (128, 128, 128, 128));
+    }
 }
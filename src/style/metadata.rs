@@ -0,0 +1,45 @@
+use std::vec::Vec;
+use std::io::{File, IoResult};
+use serialize::{json, Encodable};
+
+use json_file;
+use super::{Animation, CarInfo, ObjectInfo, SpriteNumbers};
+
+/// The parts of a style that make sense to edit by hand, serializable
+/// to and from JSON.
+#[deriving(Clone, Encodable, Decodable)]
+pub struct StyleMetadata {
+    pub sprite_numbers: SpriteNumbers,
+    pub animations: Vec<Animation>,
+    pub object_info: Vec<ObjectInfo>,
+    pub car_info: Vec<CarInfo>
+}
+
+impl StyleMetadata {
+    pub fn from_json_file(filename: &str) -> Result<StyleMetadata, String> {
+        json_file::load(&Path::new(filename))
+    }
+
+    pub fn save_json_file(&self, filename: &str) -> IoResult<()> {
+        let mut f = try!(File::create(&Path::new(filename)));
+        let mut encoder = json::PrettyEncoder::new(&mut f);
+        self.encode(&mut encoder)
+    }
+
+    /// Checks every car and object, and that the sprite numbers count
+    /// no more than the `num_sprites` sprites of the style.
+    pub fn validate(&self, num_sprites: uint) -> Result<(), String> {
+        let total = self.sprite_numbers.total();
+        if total > num_sprites {
+            return Err(format!("Sprite numbers count {} sprites, style has {}",
+                               total, num_sprites));
+        }
+        for (n, car) in self.car_info.iter().enumerate() {
+            try!(car.validate().map_err(|why| format!("Car {}: {}", n, why)));
+        }
+        for (n, obj) in self.object_info.iter().enumerate() {
+            try!(obj.validate().map_err(|why| format!("Object {}: {}", n, why)));
+        }
+        Ok(())
+    }
+}
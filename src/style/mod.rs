@@ -1,34 +1,80 @@
 
 use std::vec::Vec;
-use std::io::{File, IoResult, SeekSet};
-use std::mem::size_of;
-use piston::image::{Rgba, Luma};
+use std::io::{File, IoResult, IoError, InvalidInput, SeekSet};
+use piston::image::{GenericImage, ImageBuf, Rgba, Luma};
 
 pub use self::animation::{Animation, AreaType};
-pub use self::object_info::{ObjectInfo, Status};
-pub use self::car_info::{CarInfo, Door, VehicleType};
+pub use self::object_info::{ObjectInfo, Status, Breakage};
+pub use self::car_info::{CarInfo, Door, VehicleType, Remap, NoRemap, Remap24, Remap8};
 pub use self::sprite_info::{SpriteInfo, Delta};
-pub use self::sprite_numbers::{SpriteNumbers};
+pub use self::sprite_numbers::{SpriteNumbers, SpriteKind};
+pub use self::hls_info::HlsInfo;
+pub use self::palette_index::PaletteIndex;
+pub use self::clut::Clut;
+pub use self::error::{StyleError, StyleResult};
+pub use self::vehicle_spec::VehicleSpec;
+pub use self::metadata::StyleMetadata;
 
 use super::TileSet;
 
-use self::hls_info::HlsInfo;
-use self::palette_index::PaletteIndex;
-
 pub mod animation;
 pub mod object_info;
 pub mod car_info;
 pub mod sprite_info;
 pub mod sprite_numbers;
-
-mod hls_info;
-mod palette_index;
+pub mod hls_info;
+pub mod palette_index;
+pub mod clut;
+pub mod error;
+pub mod vehicle_spec;
+pub mod metadata;
+
+/// The width and height of a sprite graphics page.
+static SPRITE_PAGE_SIZE: uint = 256;
+/// The width and height of a tile.
+pub static TILE_SIZE: uint = 64;
 
 pub struct Style {
-    pub tiles: TileSet
+    pub format: Format,
+    pub tiles: TileSet,
+    pub num_side_tiles: uint,
+    pub num_lid_tiles: uint,
+    pub num_aux_tiles: uint,
+    pub animations: Vec<Animation>,
+    pub cluts: Clut,
+    pub palette_index: PaletteIndex,
+    pub object_info: Vec<ObjectInfo>,
+    pub car_info: Vec<CarInfo>,
+    pub sprite_info: Vec<SpriteInfo>,
+    pub sprite_graphics: Vec<u8>,
+    pub sprite_numbers: SpriteNumbers
+}
+
+/// The version number of 24-bit `.g24` styles.
+static G24_VERSION: uint = 336;
+/// The version number of 8-bit `.gry` styles.
+static GRY_VERSION: uint = 290;
+
+/// The on-disk header sizes of each style format.
+static G24_HEADER_SIZE: uint = 64;
+static GRY_HEADER_SIZE: uint = 52;
+
+/// The file format a style was loaded from.
+#[deriving(Show, PartialEq)]
+pub enum Format {
+    /// 8-bit style with a single palette and colour remap tables.
+    Gry,
+    /// 24-bit style with paged CLUTs.
+    G24
 }
 
+/// The style header.
+///
+/// Both formats are read into the same header. For `.gry` styles
+/// the CLUT region holds the palette, remap tables and remap index,
+/// so `clut_size` is their sum and the 24-bit only sizes are zero.
 struct Header {
+    format: Format,
     version: uint,
     side_size: uint,
     lid_size: uint,
@@ -40,6 +86,9 @@ struct Header {
     newcarclut_size: uint,
     fontclut_size: uint,
     palette_index_size: uint,
+    palette_size: uint,
+    remap_size: uint,
+    remap_index_size: uint,
     object_info_size: uint,
     car_info_size: uint,
     sprite_info_size: uint,
@@ -47,17 +96,27 @@ struct Header {
     sprite_numbers_size: uint
 }
 
-impl Style {
-    pub fn from_file(filename: &str) -> IoResult<Style> {
-        println!("Loading style {}", filename);
-
-        let mut f = match File::open(&Path::new(filename)) {
-            Err(why) => panic!("Could not read {}: {}", filename, why.desc),
-            Ok(file) => file
-        };
+impl Header {
+    /// Reads the header, detecting the format by its version.
+    fn read(f: &mut File) -> IoResult<Header> {
+        let version = try!(f.read_le_u32()) as uint;
+        if version == G24_VERSION {
+            Header::read_g24(f, version)
+        } else if version == GRY_VERSION {
+            Header::read_gry(f, version)
+        } else {
+            Err(IoError {
+                kind: InvalidInput,
+                desc: "Unknown style version",
+                detail: Some(format!("version {}", version))
+            })
+        }
+    }
 
-        let h = Header {
-            version: try!(f.read_le_u32()) as uint,
+    fn read_g24(f: &mut File, version: uint) -> IoResult<Header> {
+        Ok(Header {
+            format: G24,
+            version: version,
             side_size: try!(f.read_le_u32()) as uint,
             lid_size: try!(f.read_le_u32()) as uint,
             aux_size: try!(f.read_le_u32()) as uint,
@@ -68,94 +127,454 @@ impl Style {
             newcarclut_size: try!(f.read_le_u32()) as uint,
             fontclut_size: try!(f.read_le_u32()) as uint,
             palette_index_size: try!(f.read_le_u32()) as uint,
+            palette_size: 0,
+            remap_size: 0,
+            remap_index_size: 0,
             object_info_size: try!(f.read_le_u32()) as uint,
             car_info_size: try!(f.read_le_u32()) as uint,
             sprite_info_size: try!(f.read_le_u32()) as uint,
             sprite_graphics_size: try!(f.read_le_u32()) as uint,
             sprite_numbers_size: try!(f.read_le_u32()) as uint
+        })
+    }
+
+    fn read_gry(f: &mut File, version: uint) -> IoResult<Header> {
+        let side_size = try!(f.read_le_u32()) as uint;
+        let lid_size = try!(f.read_le_u32()) as uint;
+        let aux_size = try!(f.read_le_u32()) as uint;
+        let anim_size = try!(f.read_le_u32()) as uint;
+        let palette_size = try!(f.read_le_u32()) as uint;
+        let remap_size = try!(f.read_le_u32()) as uint;
+        let remap_index_size = try!(f.read_le_u32()) as uint;
+
+        Ok(Header {
+            format: Gry,
+            version: version,
+            side_size: side_size,
+            lid_size: lid_size,
+            aux_size: aux_size,
+            anim_size: anim_size,
+            clut_size: palette_size + remap_size + remap_index_size,
+            tileclut_size: 0,
+            spriteclut_size: 0,
+            newcarclut_size: 0,
+            fontclut_size: 0,
+            palette_index_size: 0,
+            palette_size: palette_size,
+            remap_size: remap_size,
+            remap_index_size: remap_index_size,
+            object_info_size: try!(f.read_le_u32()) as uint,
+            car_info_size: try!(f.read_le_u32()) as uint,
+            sprite_info_size: try!(f.read_le_u32()) as uint,
+            sprite_graphics_size: try!(f.read_le_u32()) as uint,
+            sprite_numbers_size: try!(f.read_le_u32()) as uint
+        })
+    }
+}
+
+impl Style {
+    /// Loads a `.g24` or `.gry` style. The format is detected from the
+    /// header version.
+    pub fn from_file(filename: &str) -> StyleResult<Style> {
+        println!("Loading style {}", filename);
+
+        let mut f = match File::open(&Path::new(filename)) {
+            Err(why) => return Err(StyleError::from_io("file", 0, why)),
+            Ok(file) => file
+        };
+        let file_size = match f.stat() {
+            Err(why) => return Err(StyleError::from_io("file", 0, why)),
+            Ok(stat) => stat.size as uint
         };
 
-        let mut r = StyleReader::new(&mut f, h);
-        {
-            let s_size = h.side_size as uint;
-            let l_size = h.lid_size as uint;
-            let a_size = h.aux_size as uint;
-            let pad = (4 - s_size / 4096 + l_size / 4096 + a_size / 4096) * 4096;
-            r.face_size = s_size + l_size + a_size + pad;
-
-            r.clut_size = h.clut_size;
-            if h.clut_size % 65536 != 0 {
-                r.clut_size += 65536 - (h.clut_size % 65536);
-            }
-        }
+        let h = match Header::read(&mut f) {
+            Err(why) => return Err(StyleError::from_io("header", 0, why)),
+            Ok(h) => h
+        };
 
-        let tiles = try!(r.read_tiles());
+        let mut r = StyleReader::new(&mut f, h, file_size);
+        try!(r.check_sizes());
 
-        // let faces = try!(r.read_faces());
-        // let anims = try!(r.read_anims());
-        // let cluts = try!(r.read_cluts());
-        // let pal_index = try!(r.read_palette_index());
-        // let objs = try!(r.read_object_info());
-        // let cars = try!(r.read_car_info());
-        // let sprite_info = try!(r.read_sprite_info());
-        // let sprite_gfx = try!(r.read_sprite_graphics());
-        // let sprite_numbers = try!(r.read_sprite_numbers());
-
-        // println!("{} tiles", faces.len());
-        // println!("{} animations", anims.len());
-        // println!("{} cluts", cluts.len());
-        // println!("palette index size {}", pal_index.len());
-        // println!("{} objects", objs.len());
-        // println!("{} cars", cars.len());
-        // println!("{} sprites", sprite_info.len());
-        // println!("{} sprite_graphics", sprite_gfx.len());
-        // println!("{}", sprite_numbers);
+        let tiles = try!(r.read_tiles());
+        let animations = try!(r.read_anims());
+        let cluts = try!(r.read_cluts());
+        let palette_index = try!(r.read_palette_index());
+        let object_info = try!(r.read_object_info());
+        let car_info = try!(r.read_car_info());
+        let sprite_info = try!(r.read_sprite_info());
+        let sprite_graphics = try!(r.read_sprite_graphics());
+        let sprite_numbers = try!(r.read_sprite_numbers());
+        let num_sprites = sprite_numbers.total();
+        if num_sprites > sprite_info.len() {
+            return Err(StyleError::new("sprite numbers", r.l.sprite_numbers, format!(
+                "Sprite numbers count {} sprites, but the style has {}",
+                num_sprites, sprite_info.len())));
+        }
 
         Ok(Style {
-            tiles: tiles
+            format: h.format,
+            tiles: tiles,
+            num_side_tiles: h.side_size / (TILE_SIZE * TILE_SIZE),
+            num_lid_tiles: h.lid_size / (TILE_SIZE * TILE_SIZE),
+            num_aux_tiles: h.aux_size / (TILE_SIZE * TILE_SIZE),
+            animations: animations,
+            cluts: cluts,
+            palette_index: palette_index,
+            object_info: object_info,
+            car_info: car_info,
+            sprite_info: sprite_info,
+            sprite_graphics: sprite_graphics,
+            sprite_numbers: sprite_numbers
         })
     }
+
+    /// Returns a copy of the editable style metadata.
+    pub fn metadata(&self) -> StyleMetadata {
+        StyleMetadata {
+            sprite_numbers: self.sprite_numbers.clone(),
+            animations: self.animations.clone(),
+            object_info: self.object_info.clone(),
+            car_info: self.car_info.clone()
+        }
+    }
+
+    /// Replaces the style metadata with `meta`, e.g. one loaded with
+    /// `StyleMetadata::from_json_file`. The style is left unchanged if
+    /// `meta` does not fit it.
+    pub fn set_metadata(&mut self, meta: StyleMetadata) -> Result<(), String> {
+        try!(meta.validate(self.sprite_info.len()));
+        self.sprite_numbers = meta.sprite_numbers;
+        self.animations = meta.animations;
+        self.object_info = meta.object_info;
+        self.car_info = meta.car_info;
+        Ok(())
+    }
+
+    /// Returns the total number of side, lid and aux tiles.
+    pub fn num_tiles(&self) -> uint {
+        self.num_side_tiles + self.num_lid_tiles + self.num_aux_tiles
+    }
+
+    /// Decodes tile number `tile` with remap `remap` (0-3). Colour
+    /// index 0 is transparent.
+    pub fn tile_image(&self, tile: uint, remap: uint) -> Result<ImageBuf<Rgba<u8>>, String> {
+        if tile >= self.num_tiles() || remap > 3 {
+            return Err(format!("Tile {} remap {} out of range, style has {} tiles",
+                               tile, remap, self.num_tiles()));
+        }
+
+        let palette = try!(self.palette_index.tile(tile, remap)
+                           .and_then(|p| self.check_palette(p)));
+        let tiles_per_row = self.tiles.width / TILE_SIZE;
+        let x0 = (tile % tiles_per_row * TILE_SIZE) as u32;
+        let y0 = (tile / tiles_per_row * TILE_SIZE) as u32;
+        Ok(ImageBuf::from_fn(TILE_SIZE as u32, TILE_SIZE as u32, |x, y| {
+            let Luma(index) = self.tiles.buffer.get_pixel(x0 + x, y0 + y);
+            if index == 0 {
+                Rgba(0, 0, 0, 0)
+            } else {
+                self.cluts.color(palette, index)
+            }
+        }))
+    }
+
+    /// Returns what `object` turns into when smashed, in the order
+    /// listed, each with what it turns into in turn. A type that would
+    /// turn back into one it came from is not followed again.
+    pub fn break_chain(&self, object: uint) -> Vec<Breakage> {
+        self.break_chain_from(object, &mut vec!(object))
+    }
+
+    /// Returns the object types shaped like street lamps, see
+    /// `ObjectInfo::is_lamp_post`. Map objects have one byte types, so
+    /// types past 255 are left out.
+    pub fn lamp_objects(&self) -> Vec<u8> {
+        self.object_info.iter().enumerate()
+            .filter(|&(i, info)| i <= 255 && info.is_lamp_post())
+            .map(|(i, _)| i as u8)
+            .collect()
+    }
+
+    /// Returns the breakage of `object`, reached by breaking the types
+    /// in `path`.
+    fn break_chain_from(&self, object: uint, path: &mut Vec<uint>) -> Vec<Breakage> {
+        let info = match self.object_info.as_slice().get(object) {
+            Some(info) => info,
+            None => return Vec::new()
+        };
+
+        let mut chain = Vec::with_capacity(info.into.len());
+        for into in info.into.iter() {
+            let into = *into as uint;
+            let next = if path.contains(&into) {
+                Vec::new()
+            } else {
+                path.push(into);
+                let next = self.break_chain_from(into, path);
+                path.pop();
+                next
+            };
+            chain.push(Breakage { object: into, into: next });
+        }
+        chain
+    }
+
+    /// Replaces the colour indices of tile `tile` with `img`.
+    pub fn set_tile(&mut self, tile: uint, img: &ImageBuf<Luma<u8>>) -> Result<(), String> {
+        if tile >= self.num_tiles() {
+            return Err(format!("Tile {} out of range, style has {} tiles",
+                               tile, self.num_tiles()));
+        }
+
+        let tiles_per_row = self.tiles.width / TILE_SIZE;
+        let x0 = (tile % tiles_per_row * TILE_SIZE) as u32;
+        let y0 = (tile / tiles_per_row * TILE_SIZE) as u32;
+        for y in range(0, TILE_SIZE as u32) {
+            for x in range(0, TILE_SIZE as u32) {
+                self.tiles.buffer.put_pixel(x0 + x, y0 + y, img.get_pixel(x, y));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the absolute sprite number of the `index`th sprite of
+    /// `kind`, e.g. `style.sprite(sprite_numbers::Car, car.sprite_number as uint)`.
+    pub fn sprite(&self, kind: SpriteKind, index: uint) -> Result<uint, String> {
+        self.sprite_numbers.sprite(kind, index)
+    }
+
+    /// Returns the info of absolute sprite number `sprite`.
+    pub fn sprite_info(&self, sprite: uint) -> Result<&SpriteInfo, String> {
+        if sprite >= self.sprite_info.len() {
+            return Err(format!("Sprite {} out of range, style has {}",
+                               sprite, self.sprite_info.len()));
+        }
+        Ok(&self.sprite_info[sprite])
+    }
+
+    /// Decodes absolute sprite number `sprite` with its own palette.
+    pub fn sprite_image(&self, sprite: uint) -> Result<ImageBuf<Rgba<u8>>, String> {
+        let info = try!(self.sprite_info(sprite));
+        let palette = try!(self.palette_index.sprite(info.clut as uint)
+                           .and_then(|p| self.check_palette(p)));
+        self.decode_sprite(info, palette, None)
+    }
+
+    /// Decodes the sprite of `car` recoloured with `remap`.
+    ///
+    /// `Remap24` shifts the sprite's own colours by the car's HLS
+    /// values, `Remap8` swaps its palette for a car remap palette.
+    pub fn car_sprite(&self, car: &CarInfo, remap: Remap) -> Result<ImageBuf<Rgba<u8>>, String> {
+        let sprite = try!(self.sprite(sprite_numbers::Car, car.sprite_number as uint));
+        let info = try!(self.sprite_info(sprite));
+        let own_palette = try!(self.palette_index.sprite(info.clut as uint)
+                               .and_then(|p| self.check_palette(p)));
+
+        match remap {
+            NoRemap => self.decode_sprite(info, own_palette, None),
+            Remap24(n) => {
+                if n >= car.remap24.len() {
+                    return Err(format!("Remap {} out of range", n));
+                }
+                self.decode_sprite(info, own_palette, Some(&car.remap24[n]))
+            },
+            Remap8(n) => {
+                if n >= car.remap8.len() {
+                    return Err(format!("Remap {} out of range", n));
+                }
+                let palette = try!(self.palette_index.car_remap(car.remap8[n] as uint)
+                                   .and_then(|p| self.check_palette(p)));
+                self.decode_sprite(info, palette, None)
+            }
+        }
+    }
+
+    /// Returns font palette `font` from the style's font CLUTs, for
+    /// recolouring glyphs with `Glyph::to_image`. 8-bit styles have
+    /// no font CLUTs, their fonts use the palette of the font file.
+    pub fn font_palette(&self, font: uint) -> Result<Vec<Rgba<u8>>, String> {
+        let palette = match self.palette_index.font(font) {
+            Some(p) if p < self.cluts.len() => p,
+            _ => return Err(format!("Font palette {} out of range", font))
+        };
+        Ok(range(0, 256u).map(|i| self.cluts.color(palette, i as u8)).collect())
+    }
+
+    /// Returns `palette` if it is a physical palette of the CLUT.
+    fn check_palette(&self, palette: uint) -> Result<uint, String> {
+        if palette >= self.cluts.len() {
+            return Err(format!("Palette {} out of range, style has {}",
+                               palette, self.cluts.len()));
+        }
+        Ok(palette)
+    }
+
+    /// Decodes the pixels of sprite `info` using physical `palette`,
+    /// optionally shifting every colour by `shift`. Colour index 0 is
+    /// transparent.
+    fn decode_sprite(&self, info: &SpriteInfo, palette: uint,
+                     shift: Option<&HlsInfo>) -> Result<ImageBuf<Rgba<u8>>, String> {
+        let page_offset = info.page as uint * SPRITE_PAGE_SIZE * SPRITE_PAGE_SIZE;
+        let (right, bottom) = (info.x as uint + info.width as uint,
+                               info.y as uint + info.height as uint);
+        if right > SPRITE_PAGE_SIZE || bottom > SPRITE_PAGE_SIZE ||
+           page_offset + bottom * SPRITE_PAGE_SIZE > self.sprite_graphics.len() {
+            return Err(format!("Sprite {}x{} at ({}, {}) of page {} is outside the sprite graphics",
+                               info.width, info.height, info.x, info.y, info.page));
+        }
+
+        Ok(ImageBuf::from_fn(info.width as u32, info.height as u32, |x, y| {
+            let offset = page_offset +
+                (info.y as uint + y as uint) * SPRITE_PAGE_SIZE +
+                info.x as uint + x as uint;
+            let index = self.sprite_graphics[offset];
+            if index == 0 {
+                return Rgba(0, 0, 0, 0);
+            }
+
+            let color = self.cluts.color(palette, index);
+            match shift {
+                Some(hls) => hls.apply(color),
+                None => color
+            }
+        }))
+    }
+}
+
+/// The byte offsets of the sections of a style file, computed from
+/// the header sizes.
+///
+/// The faces are padded to a whole row of four tiles and the CLUTs
+/// of 24-bit styles to whole 64K pages. In 8-bit styles the CLUT
+/// region holds the palette, remap tables and remap index.
+struct Layout {
+    faces: uint,
+    face_size: uint,
+    anims: uint,
+    cluts: uint,
+    clut_size: uint,
+    palette_index: uint,
+    object_info: uint,
+    car_info: uint,
+    sprite_info: uint,
+    sprite_graphics: uint,
+    sprite_numbers: uint,
+    end: uint
+}
+
+impl Layout {
+    fn new(h: &Header) -> Layout {
+        let header_size = match h.format {
+            G24 => G24_HEADER_SIZE,
+            Gry => GRY_HEADER_SIZE
+        };
+
+        let tile_bytes = TILE_SIZE * TILE_SIZE;
+        let num_tiles = (h.side_size + h.lid_size + h.aux_size) / tile_bytes;
+        let pad = (4 - num_tiles % 4) % 4 * tile_bytes;
+        let face_size = h.side_size + h.lid_size + h.aux_size + pad;
+
+        let clut_size = match h.format {
+            G24 if h.clut_size % clut::PAGE_SIZE != 0 => {
+                h.clut_size + clut::PAGE_SIZE - h.clut_size % clut::PAGE_SIZE
+            },
+            _ => h.clut_size
+        };
+
+        let anims = header_size + face_size;
+        let cluts = anims + h.anim_size;
+        let palette_index = cluts + clut_size;
+        let object_info = palette_index + h.palette_index_size;
+        let car_info = object_info + h.object_info_size;
+        let sprite_info = car_info + h.car_info_size;
+        let sprite_graphics = sprite_info + h.sprite_info_size;
+        let sprite_numbers = sprite_graphics + h.sprite_graphics_size;
+
+        Layout {
+            faces: header_size,
+            face_size: face_size,
+            anims: anims,
+            cluts: cluts,
+            clut_size: clut_size,
+            palette_index: palette_index,
+            object_info: object_info,
+            car_info: car_info,
+            sprite_info: sprite_info,
+            sprite_graphics: sprite_graphics,
+            sprite_numbers: sprite_numbers,
+            end: sprite_numbers + h.sprite_numbers_size
+        }
+    }
 }
 
 struct StyleReader<'a> {
     f: &'a mut File,
     h: Header,
-    header_size: uint,
-    face_size: uint,
-    clut_size: uint
+    /// The name of the section being read, for error reporting.
+    section: &'static str,
+    file_size: uint,
+    l: Layout
 }
 
 impl<'a> StyleReader<'a> {
-    fn new(f: &'a mut File, h: Header) -> StyleReader {
+    fn new(f: &'a mut File, h: Header, file_size: uint) -> StyleReader {
         StyleReader {
             f: f,
             h: h,
-            header_size: size_of::<Header>(),
-            face_size: 0,
-            clut_size: 0
+            section: "header",
+            file_size: file_size,
+            l: Layout::new(&h)
         }
     }
 
-    /// Reads all tile faces.
-    fn read_faces(&mut self) -> IoResult<Vec<u8>> {
-        let hs = self.header_size;
-        try!(self.seek(hs));
-        assert!(try!(self.tell()) == self.header_size);
+    /// Checks that the section sizes in the header add up to the
+    /// file size.
+    fn check_sizes(&self) -> StyleResult<()> {
+        let total = self.l.end;
+        if total != self.file_size {
+            return Err(StyleError::new("header", 0, format!(
+                "Section sizes add up to {} bytes, but the file is {} bytes",
+                total, self.file_size)));
+        }
+        Ok(())
+    }
 
-        let num_faces = self.h.side_size + self.h.lid_size + self.h.aux_size;
-        let mut faces = Vec::with_capacity(num_faces as uint);
-        for n in range(0, num_faces) {
-            faces.push(try!(self.read_u8()));
+    /// Starts reading `section` of `size` bytes at `offset`.
+    fn enter(&mut self, section: &'static str, offset: uint, size: uint) -> StyleResult<()> {
+        self.section = section;
+        if offset + size > self.file_size {
+            return Err(StyleError::new(section, offset, format!(
+                "{} bytes extend past the end of the file ({} bytes)",
+                size, self.file_size)));
         }
+        self.seek(offset)
+    }
+
+    /// Checks that the records of the current section, read up to the
+    /// current offset, end exactly at `end`.
+    fn leave(&mut self, end: uint) -> StyleResult<()> {
+        let offset = try!(self.tell());
+        if offset != end {
+            return Err(StyleError::new(self.section, offset, format!(
+                "Last record ends {} bytes past the section end {}", offset - end, end)));
+        }
+        Ok(())
+    }
 
-        Ok(faces)
+    /// Reads all tile faces.
+    fn read_faces(&mut self) -> StyleResult<Vec<u8>> {
+        let num_faces = self.h.side_size + self.h.lid_size + self.h.aux_size;
+        let offset = self.l.faces;
+        try!(self.enter("faces", offset, num_faces));
+
+        self.read_exact(num_faces)
     }
 
     /// Reads all block animations.
-    fn read_anims(&mut self) -> IoResult<Vec<Animation>> {
-        let offset = self.header_size + self.face_size;
-        try!(self.seek(offset));
-        assert!(try!(self.tell()) == self.header_size + self.face_size);
+    fn read_anims(&mut self) -> StyleResult<Vec<Animation>> {
+        let offset = self.l.anims;
+        let size = self.h.anim_size;
+        try!(self.enter("animations", offset, size));
 
         let num_anims = try!(self.read_u8());
         let mut anims = Vec::with_capacity(num_anims as uint);
@@ -183,190 +602,151 @@ impl<'a> StyleReader<'a> {
     }
 
     /// Reads all cluts.
-    // fn read_cluts(&mut self) -> IoResult<Vec<u8>> {
-    //     let offset = self.header_size + self.face_size + self.h.anim_size as u64;
-    //     try!(self.seek(offset));
-    //     assert!(try!(self.tell()) ==  self.header_size + self.face_size + self.h.anim_size as u64);
-
-    //     let mut paged_clut_size = self.h.clut_size;
-    //     if (self.h.clut_size % 65536 != 0) {
-    //         paged_clut_size += (65536 - (self.h.clut_size % 65536));
-    //     }
-
-    //     let mut clut = Vec::with_capacity(paged_clut_size as uint);
-    //     for n in range(0, paged_clut_size) {
-    //         clut.push(try!(self.read_u8()));
-    //     }
-
-    //     Ok(clut)
-    // }
-
-    // fn read_cluts(&mut self) -> IoResult<Vec<Vec<Vec<Rgba<u8>>>>> {
-    //     let offset = self.header_size + self.face_size + self.h.anim_size;
-    //     try!(self.seek(offset));
-    //     assert!(try!(self.tell()) == offset);
-
-    //     let num_pages = self.h.clut_size as uint / (64 * 256 * 4);
-    //     let mut clut_pages: Vec<Vec<Vec<Rgba<u8>>>> = Vec::with_capacity(num_pages);
-        
-    //     for page in range(0, num_pages) {
-    //         let num_data = 64 * 256;
-    //         let mut clut_data: Vec<Vec<Rgba<u8>>> = Vec::with_capacity(num_data);
-
-    //         for p in range(0, num_data) {
-    //             if p < 64 {
-    //                 clut_data.insert(p, Vec::new());
-    //             }
-
-    //             let b = try!(self.read_u8());
-    //             let g = try!(self.read_u8());
-    //             let r = try!(self.read_u8());
-    //             let a = try!(self.read_u8());
-
-    //             if p % 64 > clut_data.len() {
-    //                 println!("This should not happen");
-    //             }
-
-    //             clut_data.get_mut(p % 64).push(Rgba(r, g, b, a));
-    //         }
-
-    //         clut_pages.push(clut_data);
-    //     }
-
-    //     Ok(clut_pages)
-    // }
-
-    fn read_tiles(&mut self) -> IoResult<TileSet> {
+    fn read_cluts(&mut self) -> StyleResult<Clut> {
+        let offset = self.l.cluts;
+        let size = self.l.clut_size;
+        try!(self.enter("cluts", offset, size));
+
+        match self.h.format {
+            G24 => {
+                let data = try!(self.read_exact(size));
+                Ok(Clut::from_pages(data.as_slice(), self.h.clut_size / 1024))
+            },
+            Gry => {
+                let palette_size = self.h.palette_size;
+                let remap_size = self.h.remap_size;
+                if palette_size != clut::VGA_PALETTE_SIZE {
+                    return Err(StyleError::new("cluts", offset, format!(
+                        "Palette is {} bytes, expected {}", palette_size, clut::VGA_PALETTE_SIZE)));
+                }
+                if remap_size % 256 != 0 {
+                    return Err(StyleError::new("cluts", offset, format!(
+                        "Remap tables are {} bytes, not a multiple of 256", remap_size)));
+                }
+                let palette = try!(self.read_exact(palette_size));
+                let remaps = try!(self.read_exact(remap_size));
+                Ok(Clut::from_remaps(palette.as_slice(), remaps.as_slice()))
+            }
+        }
+    }
+
+    fn read_tiles(&mut self) -> StyleResult<TileSet> {
         let width: uint = 256;
-        let height: uint = self.face_size / width;
-        let tile_size: uint = 64;
+        let height: uint = self.l.face_size / width;
 
         println!("Tileset size {}x{}", width, height);
 
-        // let palette = try!(self.read_palette_index());
-        // let cluts = try!(self.read_cluts());
-
-        let header_size = self.header_size;
-        try!(self.seek(header_size));
-        // for y in range(0, height) {
-        //     for x in range(0, width) {
-        //         let color_index = try!(self.read_u8());
-        //         let tile = (((y as uint / tile_size) * width) + x as uint) / tile_size;
-        //         let clut = palette.lookup_tile(tile);
-        //     }
-        // }
-
         let tiles = try!(self.read_faces());
-        // let palette = try!(self.read_palette_index());
-
-        // println!("Index {}", palette.index.len());
-        // println!("Color {}", palette.palettes.len());
-
-        // try!(self.seek(size_of::<Header>()));
 
+        // Tiles are stored four to a row, the padding after the last
+        // tile is left blank.
         Ok(TileSet::new(width, height, |x, y| {
-            let offset = x as uint * width + y as uint;
-            let color = tiles[offset];
-            Luma(color)
+            let offset = y as uint * width + x as uint;
+            if offset < tiles.len() {
+                Luma(tiles[offset])
+            } else {
+                Luma(0)
+            }
         }))
     }
 
     /// Reads the palette index.
-    fn read_palette_index(&mut self) -> IoResult<PaletteIndex> {
-        let offset =  self.header_size + self.face_size + 
-            self.h.anim_size + self.clut_size;
-        try!(self.seek(offset));
-        assert!(try!(self.tell()) == offset);
-
-        
-        let mut index = Vec::with_capacity(self.h.palette_index_size as uint);
-        for n in range(0, self.h.palette_index_size / 2) {
-            index.push(try!(self.read_u16()));
+    fn read_palette_index(&mut self) -> StyleResult<PaletteIndex> {
+        if self.h.format == Gry {
+            return self.read_remap_index();
         }
 
-        Ok(PaletteIndex::new(index))
-    }
+        let offset = self.l.palette_index;
+        let size = self.h.palette_index_size;
+        try!(self.enter("palette index", offset, size));
 
-    /// Reads all object infos.
-    fn read_object_info(&mut self) -> IoResult<Vec<ObjectInfo>> {
-        let offset =  self.header_size + self.face_size + 
-            self.h.anim_size + self.h.clut_size + self.h.palette_index_size;
-        try!(self.seek(offset));
-        assert!(try!(self.tell()) == offset);
-
-        let mut objs = Vec::with_capacity(self.h.object_info_size as uint / 20);
-        for n in range(0, self.h.object_info_size / 20) {
-            objs.push(ObjectInfo {
-                width: try!(self.read_u32()),
-                height: try!(self.read_u32()),
-                depth: try!(self.read_u32()),
+        let mut index = Vec::with_capacity(size / 2);
+        for n in range(0, size / 2) {
+            index.push(try!(self.read_u16()));
+        }
 
-                spr_num: try!(self.read_u16()),
-                weight: try!(self.read_u16()),
-                aux: try!(self.read_u16()),
+        Ok(PaletteIndex::new(index,
+                             self.h.tileclut_size / 1024,
+                             self.h.spriteclut_size / 1024,
+                             self.h.newcarclut_size / 1024,
+                             false))
+    }
 
-                status: Status::new(try!(self.read_i8())),
-                breaks_into: try!(self.read_u8())
-            });
+    /// Reads the remap index of an 8-bit style as a palette index
+    /// into the CLUT built by `Clut::from_remaps`.
+    fn read_remap_index(&mut self) -> StyleResult<PaletteIndex> {
+        let offset = self.l.cluts + self.h.palette_size + self.h.remap_size;
+        let size = self.h.remap_index_size;
+        try!(self.enter("remap index", offset, size));
+
+        let num_remaps = self.h.remap_size / 256;
+        let mut index = Vec::with_capacity(size + 1 + num_remaps);
+        // Four remaps per tile, palette 0 is the unmapped palette.
+        for n in range(0, size) {
+            index.push(try!(self.read_u8()) as u16 + 1);
+        }
+        // All sprites share the unmapped palette.
+        index.push(0);
+        for n in range(0, num_remaps) {
+            index.push(n as u16 + 1);
         }
 
-        Ok(objs)
+        Ok(PaletteIndex::new(index, size, 1, num_remaps, true))
     }
 
-    fn read_car_info(&mut self) -> IoResult<Vec<CarInfo>> {
-        let offset =  self.header_size + self.face_size + 
-            self.h.anim_size + self.h.clut_size +
-            self.h.palette_index_size + self.h.object_info_size;
-        try!(self.seek(offset));
-        assert!(try!(self.tell()) == offset);
-
-        fn read_remap_24(f: &mut File) -> IoResult<[HlsInfo, ..12]> {
-            let mut remap24 = [HlsInfo::zero(), ..12];
-            for i in range(0, 12) {
-                remap24[i] = HlsInfo::new(
-                    try!(f.read_le_i16()),
-                    try!(f.read_le_i16()),
-                    try!(f.read_le_i16())
-                );
-            }
-            Ok(remap24)
-        }
+    /// Reads all object infos.
+    fn read_object_info(&mut self) -> StyleResult<Vec<ObjectInfo>> {
+        let offset = self.l.object_info;
+        let size = self.h.object_info_size;
+        try!(self.enter("object info", offset, size));
+
+        // Each record is 20 bytes followed by `breaks_into` object
+        // types.
+        let max_offset = offset + size;
+        let mut objs = Vec::new();
+        while try!(self.tell()) < max_offset {
+            let width = try!(self.read_u32());
+            let height = try!(self.read_u32());
+            let depth = try!(self.read_u32());
 
-        fn read_remap_8(f: &mut File) -> IoResult<[u8, ..12]> {
-            let mut remap8 = [0, ..12];
-            for i in range(0, 12) {
-                remap8[i] = try!(f.read_u8());
-            }
-            Ok(remap8)
-        }
+            let spr_num = try!(self.read_u16());
+            let weight = try!(self.read_u16());
+            let aux = try!(self.read_u16());
+
+            let status = Status::new(try!(self.read_i8()));
+            let breaks_into = try!(self.read_u8());
 
-        fn read_value(f: &mut File) -> IoResult<[u16, ..4]> {
-            let mut value = [0, ..4];
-            for i in range(0, 4) {
-                value[i] = try!(f.read_le_u16());
+            let mut into = Vec::with_capacity(breaks_into as uint);
+            for _ in range(0, breaks_into) {
+                into.push(try!(self.read_u16()));
             }
-            Ok(value)
-        }
 
-        fn read_doors(f: &mut File) -> IoResult<Vec<Door>> {
-            let mut num_doors = try!(f.read_le_i16());
-            if num_doors > 2 { num_doors = 0; }
+            objs.push(ObjectInfo {
+                width: width,
+                height: height,
+                depth: depth,
+
+                spr_num: spr_num,
+                weight: weight,
+                aux: aux,
 
-            let mut doors = Vec::with_capacity(num_doors as uint);
-            for i in range(0, num_doors) {
-                doors.push(Door {
-                    x: try!(f.read_le_i16()),
-                    y: try!(f.read_le_i16()),
-                    object: try!(f.read_le_i16()),
-                    delta :try!(f.read_le_i16())
-                });
-            }
-            Ok(doors)
+                status: status,
+                into: into
+            });
         }
+        try!(self.leave(max_offset));
+
+        Ok(objs)
+    }
+
+    fn read_car_info(&mut self) -> StyleResult<Vec<CarInfo>> {
+        let offset = self.l.car_info;
+        let size = self.h.car_info_size;
+        try!(self.enter("car info", offset, size));
 
         let mut cars = Vec::new();
 
-        let max_offset = try!(self.tell()) + self.h.car_info_size;
+        let max_offset = offset + size;
         while try!(self.tell()) < max_offset {
             cars.push(CarInfo {
                 width: try!(self.read_i16()),
@@ -383,15 +763,19 @@ impl<'a> StyleReader<'a> {
                 grip: try!(self.read_i16()),
                 handling: try!(self.read_i16()),
 
-                remap24: try!(read_remap_24(self.f)),
-                remap8: try!(read_remap_8(self.f)),
+                remap24: match self.h.format {
+                    G24 => try!(self.read_remap_24()),
+                    // 8-bit styles only have palette remaps.
+                    Gry => Vec::from_fn(car_info::NUM_REMAPS, |_| HlsInfo::zero())
+                },
+                remap8: try!(self.read_remap_8()),
 
                 vehicle_type: VehicleType::new(try!(self.read_u8())),
                 model: try!(self.read_u8()),
                 turning: try!(self.read_u8()),
                 damageable: try!(self.read_u8()),
 
-                value: try!(read_value(self.f)),
+                value: try!(self.read_value()),
 
                 cx: try!(self.read_i8()),
                 cy: try!(self.read_i8()),
@@ -421,22 +805,60 @@ impl<'a> StyleReader<'a> {
                 sound_function: try!(self.read_u8()),
                 fast_change_flag: try!(self.read_u8()),
 
-                doors: try!(read_doors(self.f))
+                doors: try!(self.read_doors())
             })
         }
+        try!(self.leave(max_offset));
 
         Ok(cars)
     }
 
-    fn read_sprite_info(&mut self) -> IoResult<Vec<SpriteInfo>> {
-        let offset =  self.header_size + self.face_size + 
-            self.h.anim_size + self.h.clut_size +
-            self.h.palette_index_size + self.h.object_info_size +
-            self.h.car_info_size;
-        try!(self.seek(offset));
-        assert!(try!(self.tell()) == offset);
+    fn read_remap_24(&mut self) -> StyleResult<Vec<HlsInfo>> {
+        let mut remap24 = Vec::with_capacity(car_info::NUM_REMAPS);
+        for i in range(0, car_info::NUM_REMAPS) {
+            remap24.push(HlsInfo::new(
+                try!(self.read_i16()),
+                try!(self.read_i16()),
+                try!(self.read_i16())
+            ));
+        }
+        Ok(remap24)
+    }
+
+    fn read_remap_8(&mut self) -> StyleResult<Vec<u8>> {
+        self.read_exact(car_info::NUM_REMAPS)
+    }
+
+    fn read_value(&mut self) -> StyleResult<Vec<u16>> {
+        let mut value = Vec::with_capacity(car_info::NUM_VALUES);
+        for i in range(0, car_info::NUM_VALUES) {
+            value.push(try!(self.read_u16()));
+        }
+        Ok(value)
+    }
+
+    fn read_doors(&mut self) -> StyleResult<Vec<Door>> {
+        let mut num_doors = try!(self.read_i16());
+        if num_doors > 2 { num_doors = 0; }
+
+        let mut doors = Vec::with_capacity(num_doors as uint);
+        for i in range(0, num_doors) {
+            doors.push(Door {
+                x: try!(self.read_i16()),
+                y: try!(self.read_i16()),
+                object: try!(self.read_i16()),
+                delta: try!(self.read_i16())
+            });
+        }
+        Ok(doors)
+    }
+
+    fn read_sprite_info(&mut self) -> StyleResult<Vec<SpriteInfo>> {
+        let offset = self.l.sprite_info;
+        let size = self.h.sprite_info_size;
+        try!(self.enter("sprite info", offset, size));
 
-        let max_offset = try!(self.tell()) + self.h.sprite_info_size;
+        let max_offset = offset + size;
         let mut sprites = Vec::new();
 
         while try!(self.tell()) < max_offset {
@@ -481,18 +903,24 @@ impl<'a> StyleReader<'a> {
                 deltas: deltas
             });
         }
+        try!(self.leave(max_offset));
 
         Ok(sprites)
     }
 
-    fn read_sprite_numbers(&mut self) -> IoResult<SpriteNumbers> {
-        let offset =  self.header_size + self.face_size + 
-            self.h.anim_size + self.h.clut_size +
-            self.h.palette_index_size + self.h.object_info_size +
-            self.h.car_info_size + self.h.sprite_info_size +
-            self.h.sprite_graphics_size;
-        //try!(self.seek(offset));
-        assert!(try!(self.tell()) == offset);
+    /// Reads the raw sprite graphics pages.
+    fn read_sprite_graphics(&mut self) -> StyleResult<Vec<u8>> {
+        let offset = self.l.sprite_graphics;
+        let size = self.h.sprite_graphics_size;
+        try!(self.enter("sprite graphics", offset, size));
+
+        self.read_exact(size)
+    }
+
+    fn read_sprite_numbers(&mut self) -> StyleResult<SpriteNumbers> {
+        let offset = self.l.sprite_numbers;
+        let size = self.h.sprite_numbers_size;
+        try!(self.enter("sprite numbers", offset, size));
 
         Ok(SpriteNumbers {
             arrow: try!(self.read_u16()),
@@ -521,41 +949,398 @@ impl<'a> StyleReader<'a> {
 }
 
 impl<'a> StyleReader<'a> {
+    /// Turns an I/O error into a `StyleError` for the current section
+    /// and offset.
+    fn check<T>(&mut self, result: IoResult<T>) -> StyleResult<T> {
+        match result {
+            Ok(v) => Ok(v),
+            Err(err) => {
+                let offset = self.f.tell().unwrap_or(0) as uint;
+                Err(StyleError::from_io(self.section, offset, err))
+            }
+        }
+    }
+
     /// Returns the current offset of the file.
-    fn tell(&self) -> IoResult<uint> {
-        Ok(try!(self.f.tell()) as uint)
+    fn tell(&mut self) -> StyleResult<uint> {
+        let r = self.f.tell();
+        Ok(try!(self.check(r)) as uint)
     }
 
     /// Seeks to the file `offset`.
-    fn seek(&mut self, offset: uint) -> IoResult<()> {
-        Ok(try!(self.f.seek(offset as i64, SeekSet)))
+    fn seek(&mut self, offset: uint) -> StyleResult<()> {
+        let r = self.f.seek(offset as i64, SeekSet);
+        self.check(r)
     }
 
-    fn read_u8(&mut self) -> IoResult<u8> {
-        Ok(try!(self.f.read_u8()))
+    fn read_exact(&mut self, len: uint) -> StyleResult<Vec<u8>> {
+        let r = self.f.read_exact(len);
+        self.check(r)
     }
 
-    fn read_i8(&mut self) -> IoResult<i8> {
-        Ok(try!(self.f.read_i8()))
+    fn read_u8(&mut self) -> StyleResult<u8> {
+        let r = self.f.read_u8();
+        self.check(r)
     }
 
-    fn read_u16(&mut self) -> IoResult<u16> {
-        Ok(try!(self.f.read_le_u16()))
+    fn read_i8(&mut self) -> StyleResult<i8> {
+        let r = self.f.read_i8();
+        self.check(r)
     }
 
-    fn read_i16(&mut self) -> IoResult<i16> {
-        Ok(try!(self.f.read_le_i16()))
+    fn read_u16(&mut self) -> StyleResult<u16> {
+        let r = self.f.read_le_u16();
+        self.check(r)
     }
 
-    fn read_u32(&mut self) -> IoResult<u32> {
-        Ok(try!(self.f.read_le_u32()))
+    fn read_i16(&mut self) -> StyleResult<i16> {
+        let r = self.f.read_le_i16();
+        self.check(r)
     }
 
-    fn read_i32(&mut self) -> IoResult<i32> {
-        Ok(try!(self.f.read_le_i32()))
+    fn read_u32(&mut self) -> StyleResult<u32> {
+        let r = self.f.read_le_u32();
+        self.check(r)
+    }
+
+    fn read_i32(&mut self) -> StyleResult<i32> {
+        let r = self.f.read_le_i32();
+        self.check(r)
+    }
+
+    fn read_f32(&mut self) -> StyleResult<f32> {
+        let r = self.f.read_le_f32();
+        self.check(r)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{File, MemWriter, TempDir};
+    use piston::image::{GenericImage, Rgba, Luma};
+
+    use super::{Style, Breakage, Header, Layout, G24, G24_VERSION, TILE_SIZE};
+    use super::{NoRemap, Remap24, Remap8, HlsInfo};
+    use super::clut;
+
+    static TILE_BYTES: uint = TILE_SIZE * TILE_SIZE;
+
+    /// Returns the header and the contents of a small `.g24` style.
+    ///
+    /// It has five tiles, so the faces are padded to a row of eight,
+    /// and 65 palettes, so the CLUT ends part way into its second
+    /// page.
+    fn g24() -> (Header, Vec<u8>) {
+        // Two side, one lid and two aux tiles. Tile 4 starts the
+        // second row, its first pixel is colour 7.
+        let mut faces = Vec::from_elem(5 * TILE_BYTES, 0u8);
+        *faces.get_mut(TILE_SIZE * 256) = 7;
+
+        // One lid animation of block 3 with frames 10 and 11.
+        let anims = vec!(1u8, 3, 1, 5, 2, 10, 11);
+
+        // Colour 1 of palette 64, the first of the second page, is
+        // stored as BGRA.
+        let mut cluts = Vec::from_elem(2 * clut::PAGE_SIZE, 0u8);
+        *cluts.get_mut(clut::PAGE_SIZE + 256) = 10;
+        *cluts.get_mut(clut::PAGE_SIZE + 256 + 1) = 20;
+        *cluts.get_mut(clut::PAGE_SIZE + 256 + 2) = 30;
+
+        // 20 tile, 2 sprite, 1 car remap and 1 font palette. Sprite
+        // palette 0 is physical palette 64.
+        let mut palette_index = MemWriter::new();
+        for n in range(0, 24u16) {
+            palette_index.write_le_u16(if n == 20 { 64 } else { n }).unwrap();
+        }
+
+        // One object that breaks into types 3 and 4.
+        let mut object_info = MemWriter::new();
+        object_info.write_le_u32(1024).unwrap();
+        object_info.write_le_u32(512).unwrap();
+        object_info.write_le_u32(256).unwrap();
+        object_info.write_le_u16(0).unwrap();
+        object_info.write_le_u16(100).unwrap();
+        object_info.write_le_u16(0).unwrap();
+        object_info.write_i8(2).unwrap();
+        object_info.write_u8(2).unwrap();
+        object_info.write_le_u16(3).unwrap();
+        object_info.write_le_u16(4).unwrap();
+
+        let car_info = car();
+
+        // One 4x2 sprite at (2, 3) of page 0 with one delta.
+        let mut sprite_info = MemWriter::new();
+        sprite_info.write(&[4, 2]).unwrap();
+        sprite_info.write_le_u16(1).unwrap();
+        sprite_info.write_u8(0).unwrap();
+        sprite_info.write_le_u16(8).unwrap();
+        sprite_info.write_le_u16(0).unwrap();
+        sprite_info.write(&[2, 3]).unwrap();
+        sprite_info.write_le_u16(0).unwrap();
+        sprite_info.write_le_u16(6).unwrap();
+        sprite_info.write_le_u32(99).unwrap();
+
+        // The first pixel of the sprite is colour 1.
+        let mut sprite_graphics = Vec::from_elem(256 * 256, 0u8);
+        *sprite_graphics.get_mut(3 * 256 + 2) = 1;
+
+        // One car sprite.
+        let mut sprite_numbers = MemWriter::new();
+        for n in range(0, 21u) {
+            sprite_numbers.write_le_u16(if n == 5 { 1 } else { 0 }).unwrap();
+        }
+
+        let h = Header {
+            format: G24,
+            version: G24_VERSION,
+            side_size: 2 * TILE_BYTES,
+            lid_size: TILE_BYTES,
+            aux_size: 2 * TILE_BYTES,
+            anim_size: anims.len(),
+            clut_size: 65 * 1024,
+            tileclut_size: 20 * 1024,
+            spriteclut_size: 2 * 1024,
+            newcarclut_size: 1024,
+            fontclut_size: 1024,
+            palette_index_size: palette_index.get_ref().len(),
+            palette_size: 0,
+            remap_size: 0,
+            remap_index_size: 0,
+            object_info_size: object_info.get_ref().len(),
+            car_info_size: car_info.len(),
+            sprite_info_size: sprite_info.get_ref().len(),
+            sprite_graphics_size: sprite_graphics.len(),
+            sprite_numbers_size: sprite_numbers.get_ref().len()
+        };
+
+        let mut f = MemWriter::new();
+        let sizes = [h.version, h.side_size, h.lid_size, h.aux_size, h.anim_size,
+                     h.clut_size, h.tileclut_size, h.spriteclut_size, h.newcarclut_size,
+                     h.fontclut_size, h.palette_index_size, h.object_info_size,
+                     h.car_info_size, h.sprite_info_size, h.sprite_graphics_size,
+                     h.sprite_numbers_size];
+        for size in sizes.iter() {
+            f.write_le_u32(*size as u32).unwrap();
+        }
+        f.write(faces.as_slice()).unwrap();
+        f.write(Vec::from_elem(3 * TILE_BYTES, 0u8).as_slice()).unwrap();
+        f.write(anims.as_slice()).unwrap();
+        f.write(cluts.as_slice()).unwrap();
+        f.write(palette_index.get_ref()).unwrap();
+        f.write(object_info.get_ref()).unwrap();
+        f.write(car_info.as_slice()).unwrap();
+        f.write(sprite_info.get_ref()).unwrap();
+        f.write(sprite_graphics.as_slice()).unwrap();
+        f.write(sprite_numbers.get_ref()).unwrap();
+
+        (h, f.unwrap())
+    }
+
+    /// Returns a 24-bit car info record with one door.
+    fn car() -> Vec<u8> {
+        let mut w = MemWriter::new();
+        // Size, sprite number, weight, speeds and handling.
+        for n in range(0, 11i16) {
+            w.write_le_i16(n * 10).unwrap();
+        }
+        // 12 HLS remaps, 12 palette remaps.
+        for n in range(0, 12i16) {
+            w.write_le_i16(n).unwrap();
+            w.write_le_i16(-n).unwrap();
+            w.write_le_i16(2 * n).unwrap();
+        }
+        for n in range(0, 12u8) {
+            w.write_u8(n + 1).unwrap();
+        }
+        // Vehicle type, model, turning and damageable.
+        w.write(&[4, 7, 1, 1]).unwrap();
+        for n in range(0, 4u16) {
+            w.write_le_u16(n * 1000).unwrap();
+        }
+        w.write(&[1, 2]).unwrap();
+        // Moment and mass.
+        w.write_le_i32(-12345).unwrap();
+        w.write_le_u32(2 * 65536).unwrap();
+        // Thrust, adhesion, friction and brake bias.
+        for _ in range(0, 6u) {
+            w.write_le_u32(65536 / 2).unwrap();
+        }
+        w.write_le_i16(5).unwrap();
+        w.write_le_i16(6).unwrap();
+        w.write_le_i16(7).unwrap();
+        w.write_le_u32(65536).unwrap();
+        w.write_le_u32(65536).unwrap();
+        // Convertible, engine, radio, horn, sound and fast change.
+        w.write(&[1, 2, 3, 4, 5, 6]).unwrap();
+        w.write_le_i16(1).unwrap();
+        for n in range(0, 4i16) {
+            w.write_le_i16(n + 20).unwrap();
+        }
+        w.unwrap()
+    }
+
+    #[test]
+    fn layout_pads_faces_and_cluts() {
+        let (h, data) = g24();
+        let l = Layout::new(&h);
+
+        assert_eq!(l.faces, 64);
+        // Five tiles padded to eight.
+        assert_eq!(l.face_size, 8 * TILE_BYTES);
+        assert_eq!(l.anims, 64 + 32768);
+        assert_eq!(l.cluts, 32832 + 7);
+        // 65K of palettes padded to two 64K pages.
+        assert_eq!(l.clut_size, 2 * clut::PAGE_SIZE);
+        assert_eq!(l.palette_index, 32839 + 131072);
+        assert_eq!(l.object_info, 163911 + 48);
+        assert_eq!(l.car_info, 163959 + 24);
+        assert_eq!(l.sprite_info, 163983 + 182);
+        assert_eq!(l.sprite_graphics, 164165 + 19);
+        assert_eq!(l.sprite_numbers, 164184 + 65536);
+        assert_eq!(l.end, 229720 + 42);
+        assert_eq!(l.end, data.len());
+    }
+
+    #[test]
+    fn reads_every_section() {
+        let (_, data) = g24();
+        let dir = TempDir::new("style").unwrap();
+        let path = dir.path().join("test.g24");
+        let mut f = File::create(&path).unwrap();
+        f.write(data.as_slice()).unwrap();
+
+        let style = match Style::from_file(path.as_str().unwrap()) {
+            Ok(style) => style,
+            Err(why) => panic!("{}", why)
+        };
+
+        assert!(style.format == G24);
+        assert_eq!((style.num_side_tiles, style.num_lid_tiles, style.num_aux_tiles), (2, 1, 2));
+        assert_eq!((style.tiles.width, style.tiles.height), (256, 128));
+        let Luma(index) = style.tiles.buffer.get_pixel(0, 64);
+        assert_eq!(index, 7);
+
+        assert_eq!(style.animations.len(), 1);
+        assert_eq!(style.animations[0].block, 3);
+        assert_eq!(style.animations[0].speed, 5);
+        assert_eq!(style.animations[0].frames, vec!(10, 11));
+
+        assert_eq!(style.cluts.len(), 65);
+        let Rgba(r, g, b, a) = style.cluts.color(64, 1);
+        assert_eq!((r, g, b, a), (30, 20, 10, 255));
+        assert_eq!(style.palette_index.sprite(0), Ok(64));
+        assert_eq!(style.palette_index.font(0), Some(23));
+
+        assert_eq!(style.object_info.len(), 1);
+        let obj = &style.object_info[0];
+        assert_eq!((obj.width, obj.height, obj.depth, obj.weight), (1024, 512, 256, 100));
+        assert_eq!(obj.into, vec!(3, 4));
+        assert_eq!(obj.breaks_into(), 2);
+        // Types 3 and 4 have no info, so they break no further.
+        assert_eq!(style.break_chain(0), vec!(Breakage { object: 3, into: Vec::new() },
+                                              Breakage { object: 4, into: Vec::new() }));
+
+        assert_eq!(style.car_info.len(), 1);
+        let car = &style.car_info[0];
+        assert_eq!((car.width, car.handling), (0, 100));
+        assert_eq!(car.remap24.len(), 12);
+        assert_eq!((car.remap24[11].h, car.remap24[11].l, car.remap24[11].s), (11, -11, 22));
+        assert_eq!(car.remap8, vec!(1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12));
+        assert_eq!((car.model, car.value[3]), (7, 3000));
+        assert_eq!((car.cx, car.cy, car.moment), (1, 2, -12345));
+        assert_eq!((car.mass, car.front_brake_bias), (2.0, 0.5));
+        assert_eq!((car.turn_ratio, car.steering_wheel_offset), (5, 7));
+        assert!(car.convertible);
+        assert_eq!(car.fast_change_flag, 6);
+        assert_eq!(car.doors.len(), 1);
+        assert_eq!((car.doors[0].x, car.doors[0].delta), (20, 23));
+
+        assert_eq!(style.sprite_info.len(), 1);
+        let sprite = &style.sprite_info[0];
+        assert_eq!((sprite.width, sprite.height, sprite.x, sprite.y), (4, 2, 2, 3));
+        assert_eq!(sprite.deltas.len(), 1);
+        assert_eq!(sprite.deltas[0].w, 99);
+        assert_eq!(style.sprite_graphics.len(), 256 * 256);
+        assert_eq!(style.sprite_numbers.car, 1);
+
+        let img = match style.sprite_image(0) {
+            Ok(img) => img,
+            Err(why) => panic!("{}", why)
+        };
+        let Rgba(r, g, b, a) = img.get_pixel(0, 0);
+        assert_eq!((r, g, b, a), (30, 20, 10, 255));
+        let Rgba(_, _, _, a) = img.get_pixel(1, 0);
+        assert_eq!(a, 0);
+    }
+
+    #[test]
+    fn car_sprites_pick_the_remap() {
+        let (_, data) = g24();
+        let dir = TempDir::new("style").unwrap();
+        let path = dir.path().join("test.g24");
+        File::create(&path).unwrap().write(data.as_slice()).unwrap();
+        let style = Style::from_file(path.as_str().unwrap()).unwrap();
+        let car = &style.car_info[0];
+
+        let pixel = |remap| match style.car_sprite(car, remap) {
+            Ok(img) => {
+                let Rgba(r, g, b, a) = img.get_pixel(0, 0);
+                (r, g, b, a)
+            },
+            Err(why) => panic!("{}", why)
+        };
+        assert_eq!(pixel(NoRemap), (30, 20, 10, 255));
+
+        // Remap24 shifts the sprite's own palette 64.
+        let Rgba(r, g, b, a) = HlsInfo::new(11, -11, 22).apply(Rgba(30, 20, 10, 255));
+        assert_eq!(pixel(Remap24(11)), (r, g, b, a));
+
+        // Remap8 0 is car remap palette 1, virtual palette 23, which
+        // is black.
+        assert_eq!(pixel(Remap8(0)), (0, 0, 0, 255));
+
+        assert!(style.car_sprite(car, Remap24(12)).is_err());
+        assert!(style.car_sprite(car, Remap8(12)).is_err());
+    }
+
+    #[test]
+    fn records_must_end_with_their_section() {
+        let (h, mut data) = g24();
+        // The object claims a third breakage, which runs into the car
+        // info.
+        let breaks_into = Layout::new(&h).object_info + 19;
+        *data.get_mut(breaks_into) = 3;
+
+        let dir = TempDir::new("style").unwrap();
+        let path = dir.path().join("test.g24");
+        File::create(&path).unwrap().write(data.as_slice()).unwrap();
+        match Style::from_file(path.as_str().unwrap()) {
+            Ok(_) => panic!("read past the object info"),
+            Err(why) => assert_eq!(why.section, "object info")
+        }
     }
 
-    fn read_f32(&mut self) -> IoResult<f32> {
-        Ok(try!(self.f.read_le_f32()))
+    #[test]
+    fn break_chains_stop_at_cycles() {
+        let (_, data) = g24();
+        let dir = TempDir::new("style").unwrap();
+        let path = dir.path().join("test.g24");
+        File::create(&path).unwrap().write(data.as_slice()).unwrap();
+        let mut style = Style::from_file(path.as_str().unwrap()).unwrap();
+
+        // 0 breaks into 1, 1 back into 0 and into 2, 2 into itself.
+        let obj = style.object_info[0].clone();
+        style.object_info = vec!(obj.clone(), obj.clone(), obj);
+        style.object_info.get_mut(0).into = vec!(1);
+        style.object_info.get_mut(1).into = vec!(2, 0);
+        style.object_info.get_mut(2).into = vec!(2);
+
+        let leaf = |object| Breakage { object: object, into: Vec::new() };
+        assert_eq!(style.break_chain(0), vec!(
+            Breakage { object: 1, into: vec!(
+                Breakage { object: 2, into: vec!(leaf(2)) },
+                leaf(0))
+            }));
+        assert_eq!(style.break_chain(2), vec!(leaf(2)));
     }
 }
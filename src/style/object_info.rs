@@ -1,3 +1,6 @@
+use map::object::UNITS_PER_BLOCK;
+
+#[deriving(Clone, Encodable, Decodable)]
 pub struct ObjectInfo {
     pub width: u32, 
     pub height: u32,
@@ -6,11 +9,56 @@ pub struct ObjectInfo {
     pub weight: u16,
     pub aux: u16,
     pub status: Status,
-    pub breaks_into: u8,
-    //pub into: Vec<u16>
+    /// The object types this object turns into when smashed.
+    pub into: Vec<u16>
+}
+
+impl ObjectInfo {
+    /// Returns the number of object types the object turns into.
+    pub fn breaks_into(&self) -> uint {
+        self.into.len()
+    }
+
+    /// Checks that `into` fits the one byte count of the style format.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.into.len() > 255 {
+            return Err(format!("Breaks into {} objects, at most 255 fit",
+                               self.into.len()));
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if the object turns into other objects when
+    /// smashed.
+    pub fn is_breakable(&self) -> bool {
+        !self.into.is_empty()
+    }
+
+    /// Returns `true` if the object is shaped like a street lamp: a
+    /// visible post at least a block tall on a footprint no wider than
+    /// a quarter of a block.
+    pub fn is_lamp_post(&self) -> bool {
+        let post = (UNITS_PER_BLOCK / 4.0) as u32;
+        let solid = match self.status {
+            Ignorable | Invisible => false,
+            _ => true
+        };
+        solid && self.width <= post && self.depth <= post &&
+            self.height >= UNITS_PER_BLOCK as u32
+    }
+}
+
+/// An object type and what it turns into when smashed, as returned by
+/// `Style::break_chain`.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Breakage {
+    pub object: uint,
+    /// What the object turns into, in the order listed.
+    pub into: Vec<Breakage>
 }
 
 /// A descriptor for an object, which determines how it behaves.
+#[deriving(Clone, Encodable, Decodable)]
 pub enum Status {
     Normal,
     /// Can be driven over.
@@ -38,3 +86,37 @@ impl Status {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{ObjectInfo, Status, Normal, Invisible};
+
+    fn object(width: u32, height: u32, depth: u32, status: Status) -> ObjectInfo {
+        ObjectInfo {
+            width: width,
+            height: height,
+            depth: depth,
+            spr_num: 0,
+            weight: 0,
+            aux: 0,
+            status: status,
+            into: Vec::new()
+        }
+    }
+
+    #[test]
+    fn tall_thin_posts_are_lamps() {
+        assert!(object(8, 128, 8, Normal).is_lamp_post());
+        assert!(object(16, 64, 16, Normal).is_lamp_post());
+    }
+
+    #[test]
+    fn other_shapes_are_not_lamps() {
+        // Too short.
+        assert!(!object(8, 32, 8, Normal).is_lamp_post());
+        // Too wide, like a bin or a crate.
+        assert!(!object(48, 128, 8, Normal).is_lamp_post());
+        assert!(!object(8, 128, 48, Normal).is_lamp_post());
+        assert!(!object(8, 128, 8, Invisible).is_lamp_post());
+    }
+}
@@ -1,19 +1,60 @@
-use piston::image::{
-    Rgba
-};
+use std::vec::Vec;
 
+/// Maps virtual palette numbers to physical palettes in the CLUT.
+///
+/// The virtual palettes are laid out as tile palettes first (four
+/// per tile, one for each remap), then sprite palettes and finally
+/// the car remap palettes and the font palettes.
 pub struct PaletteIndex {
     pub index: Vec<u16>,
+    tile_cluts: uint,
+    sprite_cluts: uint,
+    car_cluts: uint,
+    /// Whether all sprites share sprite palette 0, as in 8-bit styles.
+    shared_sprite_palette: bool
 }
 
 impl PaletteIndex {
-    pub fn new(index: Vec<u16>) -> PaletteIndex {
+    pub fn new(index: Vec<u16>, tile_cluts: uint, sprite_cluts: uint,
+               car_cluts: uint, shared_sprite_palette: bool) -> PaletteIndex {
         PaletteIndex {
-            index: index
+            index: index,
+            tile_cluts: tile_cluts,
+            sprite_cluts: sprite_cluts,
+            car_cluts: car_cluts,
+            shared_sprite_palette: shared_sprite_palette
         }
     }
 
-    pub fn look_tile(&self, tile: uint) -> uint {
-        self.index[4 * tile] as uint
+    /// Returns the physical palette of `tile` with `remap` applied.
+    pub fn tile(&self, tile: uint, remap: uint) -> Result<uint, String> {
+        self.get(4 * tile + remap)
+    }
+
+    /// Returns the physical palette of sprite palette `clut`.
+    pub fn sprite(&self, clut: uint) -> Result<uint, String> {
+        let clut = if self.shared_sprite_palette { 0 } else { clut };
+        self.get(self.tile_cluts + clut)
+    }
+
+    /// Returns the physical palette of car remap palette `remap`.
+    pub fn car_remap(&self, remap: uint) -> Result<uint, String> {
+        self.get(self.tile_cluts + self.sprite_cluts + remap)
+    }
+
+    /// Returns the physical palette of font palette `font`, if the
+    /// style has one.
+    pub fn font(&self, font: uint) -> Option<uint> {
+        let number = self.tile_cluts + self.sprite_cluts + self.car_cluts + font;
+        self.index.as_slice().get(number).map(|p| *p as uint)
+    }
+
+    /// Returns the physical palette of virtual palette `number`.
+    fn get(&self, number: uint) -> Result<uint, String> {
+        match self.index.as_slice().get(number) {
+            Some(p) => Ok(*p as uint),
+            None => Err(format!("Virtual palette {} out of range, palette index has {}",
+                                number, self.index.len()))
+        }
     }
 }
@@ -4,7 +4,7 @@
 /// Each of these numbers stores the number of sprites of that
 /// particular type. The number can be zero if there are no sprites of
 /// that type in the style.
-#[deriving(Show)]
+#[deriving(Show, Clone, Encodable, Decodable)]
 pub struct SpriteNumbers {
     pub arrow: u16,
     pub digits: u16,
@@ -28,3 +28,123 @@ pub struct SpriteNumbers {
     pub tumtruck: u16,
     pub ferry: u16
 }
+
+/// A sprite category. The categories are listed in the order their
+/// sprites are stored in the style.
+#[deriving(Show, PartialEq, Clone)]
+pub enum SpriteKind {
+    Arrow,
+    Digits,
+    Boat,
+    Case,
+    Bus,
+    Car,
+    Object,
+    Pedestrian,
+    Speedo,
+    Tank,
+    TrafficLights,
+    Train,
+    TrainDoors,
+    Bike,
+    Tram,
+    WreckedBus,
+    WreckedCar,
+    Ex,
+    TumCar,
+    TumTruck,
+    Ferry
+}
+
+static KINDS: [SpriteKind, ..21] = [
+    Arrow, Digits, Boat, Case, Bus, Car, Object, Pedestrian, Speedo, Tank,
+    TrafficLights, Train, TrainDoors, Bike, Tram, WreckedBus, WreckedCar,
+    Ex, TumCar, TumTruck, Ferry
+];
+
+impl SpriteNumbers {
+    /// Returns the number of sprites of `kind`.
+    pub fn count(&self, kind: SpriteKind) -> u16 {
+        match kind {
+            Arrow => self.arrow,
+            Digits => self.digits,
+            Boat => self.boat,
+            Case => self.case,
+            Bus => self.bus,
+            Car => self.car,
+            Object => self.object,
+            Pedestrian => self.pedestrian,
+            Speedo => self.speedo,
+            Tank => self.tank,
+            TrafficLights => self.traffic_lights,
+            Train => self.train,
+            TrainDoors => self.trdoors,
+            Bike => self.bike,
+            Tram => self.tram,
+            WreckedBus => self.wrecked_bus,
+            WreckedCar => self.wrecked_car,
+            Ex => self.ex,
+            TumCar => self.tumcar,
+            TumTruck => self.tumtruck,
+            Ferry => self.ferry
+        }
+    }
+
+    /// Returns the absolute sprite number of the first sprite of
+    /// `kind`.
+    pub fn first(&self, kind: SpriteKind) -> uint {
+        KINDS.iter()
+            .take_while(|k| **k != kind)
+            .fold(0, |sum, k| sum + self.count(*k) as uint)
+    }
+
+    /// Returns the total number of sprites in the style.
+    pub fn total(&self) -> uint {
+        KINDS.iter().fold(0, |sum, k| sum + self.count(*k) as uint)
+    }
+
+    /// Returns the absolute sprite number of the `index`th sprite of
+    /// `kind`.
+    pub fn sprite(&self, kind: SpriteKind, index: uint) -> Result<uint, String> {
+        let count = self.count(kind) as uint;
+        if index >= count {
+            return Err(format!("{} sprite {} out of range, style has {}",
+                               kind, index, count));
+        }
+        Ok(self.first(kind) + index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SpriteNumbers, Arrow, Digits, Car, Object, Ferry};
+
+    fn numbers() -> SpriteNumbers {
+        SpriteNumbers {
+            arrow: 2, digits: 10, boat: 0, case: 1, bus: 3, car: 5, object: 7,
+            pedestrian: 0, speedo: 0, tank: 0, traffic_lights: 0, train: 0,
+            trdoors: 0, bike: 0, tram: 0, wrecked_bus: 0, wrecked_car: 0,
+            ex: 0, tumcar: 0, tumtruck: 0, ferry: 4
+        }
+    }
+
+    #[test]
+    fn sprites_follow_the_earlier_categories() {
+        let n = numbers();
+        assert_eq!(n.sprite(Arrow, 0), Ok(0));
+        assert_eq!(n.sprite(Digits, 9), Ok(11));
+        // Boat is empty, case 1 and bus 3 come before the cars.
+        assert_eq!(n.sprite(Car, 0), Ok(16));
+        assert_eq!(n.sprite(Object, 6), Ok(27));
+        assert_eq!(n.sprite(Ferry, 3), Ok(31));
+        assert_eq!(n.total(), 32);
+    }
+
+    #[test]
+    fn rejects_indices_past_the_category() {
+        let n = numbers();
+        assert!(n.sprite(Arrow, 2).is_err());
+        assert!(n.sprite(Car, 5).is_err());
+        assert!(n.sprite(Ferry, 4).is_err());
+    }
+}
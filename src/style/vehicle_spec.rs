@@ -0,0 +1,113 @@
+use std::f32::consts::PI;
+
+use super::car_info::CarInfo;
+
+/// The number of pixels along the side of a block.
+pub static PIXELS_PER_BLOCK: f32 = 64.0;
+/// The number of angle units in a full turn, as in map rotations.
+pub static ANGLE_UNITS_PER_TURN: f32 = 1024.0;
+
+// The format gives no real-world units, the scales below are guesses.
+
+/// The length of a block side in metres. A guess.
+pub static METRES_PER_BLOCK: f32 = 4.0;
+/// The number of physics ticks per second. A guess.
+pub static TICKS_PER_SECOND: f32 = 30.0;
+/// The number of speed units in a pixel per tick. A guess, tuned so
+/// that ordinary cars top out at about 80 km/h.
+pub static SPEED_UNITS_PER_PIXEL: f32 = 8.0;
+/// The number of kg in a unit of `CarInfo::mass`. A guess, taking the
+/// mass to be in tonnes.
+pub static KG_PER_MASS_UNIT: f32 = 1000.0;
+
+static GRAVITY: f32 = 9.81;
+
+/// The handling of a car in SI units, derived from its `CarInfo`.
+///
+/// Lengths are converted from pixels with `METRES_PER_BLOCK /
+/// PIXELS_PER_BLOCK`, speeds from `SPEED_UNITS_PER_PIXEL`ths of a pixel
+/// per tick, angles from `ANGLE_UNITS_PER_TURN`ths of a turn and the
+/// mass with `KG_PER_MASS_UNIT`. Only the angle scale is known, the
+/// others are guesses. Friction, adhesion and slide values are ratios
+/// and are only scaled from fixed-point. The moment of inertia is left
+/// out, as its units are unknown; it stays in `CarInfo::moment`.
+pub struct VehicleSpec {
+    /// Width in metres.
+    pub width: f32,
+    /// Length in metres.
+    pub length: f32,
+    /// Height in metres.
+    pub height: f32,
+
+    /// Mass in kg.
+    pub mass: f32,
+    /// Centre of mass relative to the graphical centre, in metres.
+    pub centre_of_mass: [f32, ..2],
+
+    /// Top speed in m/s.
+    pub max_speed: f32,
+    /// Top reversing speed in m/s.
+    pub min_speed: f32,
+    /// Acceleration in m/s².
+    pub acceleration: f32,
+    /// Engine force in N, the gear thrust ratio times the car's
+    /// weight.
+    pub thrust: f32,
+
+    pub tyre_adhesion_x: f32,
+    pub tyre_adhesion_y: f32,
+    pub handbrake_friction: f32,
+    pub footbrake_friction: f32,
+    /// The share of braking done by the front wheels, 0-1.
+    pub front_brake_bias: f32,
+
+    /// Steering lock in radians.
+    pub turn_ratio: f32,
+    /// Distance of the driven wheels from the centre, in metres.
+    pub drive_wheel_offset: f32,
+    /// Distance of the steered wheels from the centre, in metres.
+    pub steering_wheel_offset: f32,
+    pub back_end_slide_value: f32,
+    pub handbrake_slide_value: f32
+}
+
+impl VehicleSpec {
+    pub fn from_car_info(car: &CarInfo) -> VehicleSpec {
+        let mass = car.mass * KG_PER_MASS_UNIT;
+        VehicleSpec {
+            width: metres(car.width as f32),
+            length: metres(car.height as f32),
+            height: metres(car.depth as f32),
+
+            mass: mass,
+            centre_of_mass: [metres(car.cx as f32), metres(car.cy as f32)],
+
+            max_speed: metres_per_second(car.max_speed as f32),
+            min_speed: metres_per_second(car.min_speed as f32),
+            acceleration: metres_per_second(car.acceleration as f32) * TICKS_PER_SECOND,
+            thrust: car.gear_thrust_ratio * mass * GRAVITY,
+
+            tyre_adhesion_x: car.tyre_adhesion_x,
+            tyre_adhesion_y: car.tyre_adhesion_y,
+            handbrake_friction: car.handbrake_friction,
+            footbrake_friction: car.footbrake_friction,
+            front_brake_bias: car.front_brake_bias,
+
+            turn_ratio: car.turn_ratio as f32 / ANGLE_UNITS_PER_TURN * 2.0 * PI,
+            drive_wheel_offset: metres(car.drive_wheel_offset as f32),
+            steering_wheel_offset: metres(car.steering_wheel_offset as f32),
+            back_end_slide_value: car.back_end_slide_value,
+            handbrake_slide_value: car.handbrake_slide_value
+        }
+    }
+}
+
+/// Converts pixels to metres.
+fn metres(pixels: f32) -> f32 {
+    pixels * METRES_PER_BLOCK / PIXELS_PER_BLOCK
+}
+
+/// Converts speed units to m/s.
+fn metres_per_second(speed: f32) -> f32 {
+    metres(speed / SPEED_UNITS_PER_PIXEL) * TICKS_PER_SECOND
+}